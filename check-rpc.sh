@@ -2,7 +2,8 @@
 
 # set -e
 
-gi_result=$(bitcoin-cli getrpcinfo 2>&1)
+# bitcoin.conf sets the network and the port bitcoind serves RPC on
+gi_result=$(bitcoin-cli -conf=/root/.bitcoin/bitcoin.conf getrpcinfo 2>&1)
 error_code=$?
 
 if [ "$error_code" -eq 28 ]; then
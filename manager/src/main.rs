@@ -1,9 +1,13 @@
 use std::convert::TryFrom;
 use std::env::var;
 use std::error::Error;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::os::unix::net::UnixListener;
 use std::os::unix::prelude::ExitStatusExt;
+use std::process::Output;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{borrow::Cow, sync::Mutex};
 use std::{fs, io::Write, path::Path};
 
@@ -17,18 +21,85 @@ use tmpl::TemplatingReader;
 
 lazy_static::lazy_static! {
     static ref CHILD_PID: Mutex<Option<u32>> = Mutex::new(None);
+    static ref LATEST_STATS: Mutex<Option<Stats>> = Mutex::new(None);
+    /// Where bitcoind's PID is written for external supervisors, if configured.
+    static ref PIDFILE: Mutex<Option<std::path::PathBuf>> = Mutex::new(None);
+    /// Held while writing the stats files, which both the sidecar and the stale stats
+    /// watchdog do. Holds when they were last written by the sidecar, as a unix timestamp,
+    /// or 0 if never.
+    static ref STATS_FILES: Mutex<u64> = Mutex::new(0);
 }
+/// The network bitcoind runs on when none is configured.
+const DEFAULT_CHAIN: &str = "testnet4";
+/// Chain data directories left out of backups unless configured otherwise.
+const DEFAULT_BACKUP_IGNORE: &[&str] = &["blocks", "chainstate", "indexes"];
+const RESCAN_LOCK: &str = "/root/.bitcoin/start9/rescan.lock";
+/// Exit code used when the installed bitcoind is older than the configured minimum.
+const VERSION_TOO_OLD_EXIT_CODE: i32 = 78;
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+/// Set by the sidecar when bitcoind should be respawned after it exits.
+static RESTART_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// How long bitcoind gets to exit after SIGTERM before it is killed. This must stay
+/// below the `sigterm-timeout` in the manifest, or StartOS kills the container first.
+static SHUTDOWN_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(DEFAULT_SHUTDOWN_TIMEOUT_SECS);
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 270;
 
 #[derive(Clone, Debug, serde::Deserialize)]
 pub struct ChainInfo {
+    #[serde(default)]
+    chain: String,
     blocks: usize,
     headers: usize,
+    bestblockhash: String,
     verificationprogress: f64,
     size_on_disk: u64,
     #[serde(default)]
     pruneheight: usize,
     #[serde(default)]
     softforks: LinearMap<String, SoftFork>,
+    /// Only reported by versions of bitcoind that expose the active assumevalid block.
+    #[serde(default)]
+    assumevalid: Option<String>,
+    #[serde(default)]
+    warnings: Warnings,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct BlockStats {
+    /// The 10th, 25th, 50th, 75th and 90th percentile fee rates in sat/vB.
+    feerate_percentiles: [f64; 5],
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct ZmqNotification {
+    #[serde(rename = "type")]
+    kind: String,
+    address: String,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct PeerInfo {
+    /// The peer's best height when the connection was opened, or -1 if unknown.
+    #[serde(default)]
+    startingheight: i64,
+    /// The network the peer is reached over: ipv4, ipv6, onion, i2p, cjdns or not_publicly_routable.
+    #[serde(default)]
+    network: String,
+    /// e.g. outbound-full-relay, block-relay-only or inbound.
+    #[serde(default)]
+    connection_type: String,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct ChainTip {
+    height: usize,
+    hash: String,
+    status: String,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct BlockHeader {
+    time: u64,
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
@@ -36,6 +107,134 @@ pub struct NetworkInfo {
     connections: usize,
     connections_in: usize,
     connections_out: usize,
+    #[serde(default)]
+    timeoffset: i64,
+    #[serde(default)]
+    localaddresses: Vec<LocalAddress>,
+    #[serde(default)]
+    localservicesnames: Vec<String>,
+    #[serde(default)]
+    subversion: String,
+    #[serde(default)]
+    warnings: Warnings,
+}
+
+/// A single string with `-deprecatedrpc=warnings`, a list of warnings otherwise.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Warnings {
+    Legacy(String),
+    List(Vec<String>),
+}
+
+impl Default for Warnings {
+    fn default() -> Self {
+        Warnings::List(Vec::new())
+    }
+}
+
+impl Warnings {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Warnings::Legacy(warning) if warning.is_empty() => Vec::new(),
+            Warnings::Legacy(warning) => vec![warning],
+            Warnings::List(warnings) => warnings,
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct LocalAddress {
+    pub address: String,
+    pub port: u16,
+    pub score: usize,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct ChainStates {
+    chainstates: Vec<ChainState>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct ChainState {
+    #[serde(default)]
+    snapshot_blockhash: Option<String>,
+    #[serde(default)]
+    validated: bool,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct NetTotals {
+    totalbytesrecv: u64,
+    totalbytessent: u64,
+    timemillis: u64,
+    uploadtarget: UploadTarget,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct UploadTarget {
+    timeframe: u64,
+    target: u64,
+    target_reached: bool,
+    bytes_left_in_cycle: u64,
+    time_left_in_cycle: u64,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct TxOutSetInfo {
+    txouts: u64,
+    /// Only reported when the stats come from coinstatsindex.
+    #[serde(default)]
+    total_unspendable_amount: Option<f64>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct IndexStatus {
+    synced: bool,
+    best_block_height: usize,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct MempoolInfo {
+    size: u64,
+    bytes: u64,
+    usage: u64,
+    mempoolminfee: f64,
+    maxmempool: u64,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct SmartFee {
+    #[serde(default)]
+    feerate: Option<f64>,
+    #[serde(default)]
+    errors: Vec<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct WalletInfo {
+    txcount: u64,
+    #[serde(default)]
+    scanning: Scanning,
+}
+
+/// `false` when the wallet isn't rescanning.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Scanning {
+    Idle(bool),
+    Active { duration: u64, progress: f64 },
+}
+
+impl Default for Scanning {
+    fn default() -> Self {
+        Scanning::Idle(false)
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct DeploymentInfo {
+    deployments: LinearMap<String, SoftFork>,
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
@@ -45,43 +244,22 @@ pub enum SoftFork {
     Buried { active: bool, height: usize },
     #[serde(rename = "bip9")]
     Bip9 { active: bool, bip9: Bip9 },
+    #[serde(other)]
+    Unknown,
 }
 
+/// A BIP9 deployment. Statuses are kept as reported so that deployments added in future
+/// versions of bitcoind are shown too.
 #[derive(Clone, Debug, serde::Deserialize)]
-#[serde(tag = "status")]
-pub enum Bip9 {
-    #[serde(rename = "defined")]
-    Defined {
-        start_time: u64,
-        timeout: u64,
-        since: usize,
-    },
-    #[serde(rename = "started")]
-    Started {
-        bit: usize,
-        start_time: u64,
-        timeout: u64,
-        since: usize,
-        statistics: Bip9Stats,
-    },
-    #[serde(rename = "locked_in")]
-    LockedIn {
-        start_time: u64,
-        timeout: u64,
-        since: usize,
-    },
-    #[serde(rename = "active")]
-    Active {
-        start_time: u64,
-        timeout: u64,
-        since: usize,
-    },
-    #[serde(rename = "failed")]
-    Failed {
-        start_time: u64,
-        timeout: u64,
-        since: usize,
-    },
+pub struct Bip9 {
+    status: String,
+    /// -1 for deployments that are always active
+    start_time: i64,
+    /// `i64::MAX` for deployments that never time out
+    timeout: i64,
+    since: usize,
+    #[serde(default)]
+    statistics: Option<Bip9Stats>,
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
@@ -110,8 +288,589 @@ pub struct Stat {
     masked: bool,
 }
 
-fn sidecar(config: &Mapping, addr: &str) -> Result<(), Box<dyn Error>> {
+/// Filesystems known to cause database corruption when backing bitcoind's data directory.
+const PROBLEMATIC_FILESYSTEMS: &[&str] = &["nfs", "nfs4", "cifs", "smb3", "fuse.sshfs", "9p"];
+
+/// An RPC call slower than this is taken as a sign that bitcoind is busy.
+const SLOW_RPC_THRESHOLD: Duration = Duration::from_secs(2);
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 5;
+/// Polling any faster than this only adds RPC load without fresher stats.
+const MIN_POLL_INTERVAL_SECS: u64 = 1;
+/// How much to stretch the poll interval by while bitcoind is busy.
+const LOADED_POLL_MULTIPLIER: u32 = 4;
+/// Caps the stats write backoff at 16 times the poll interval.
+const MAX_WRITE_BACKOFF_DOUBLINGS: u32 = 4;
+/// How long the RPC proxy waits on a peer before trying another.
+const DEFAULT_PROXY_PEER_TIMEOUT_SECS: u64 = 30;
+/// How long the RPC proxy keeps its list of peers before refreshing it.
+const DEFAULT_PROXY_MAX_PEER_AGE_SECS: u64 = 300;
+/// How long the RPC proxy sheds requests once bitcoind reports its work queue full.
+const DEFAULT_PROXY_BACKPRESSURE_SECS: u64 = 2;
+/// bitcoind's default `-rpcthreads` and `-rpcworkqueue`.
+const DEFAULT_RPC_THREADS: u64 = 16;
+const DEFAULT_RPC_WORKQUEUE: u64 = 128;
+/// How many poll intervals may pass without a stats update before it is flagged stale.
+const STALE_STATS_POLLS: u32 = 3;
+/// The score bitcoind gives an address passed with `-externalip` (`LOCAL_MANUAL`).
+const LOCAL_MANUAL_SCORE: usize = 4;
+
+/// Values carried over between sidecar polls.
+#[derive(Debug, Default)]
+pub struct SidecarState {
+    last_net_totals: Option<NetTotals>,
+    under_load: bool,
+    /// The bitcoind process of the current session and the height it was first seen at.
+    session_start: Option<(Option<u32>, usize)>,
+    slowest_rpc: Option<(String, Duration)>,
+    /// The last block height reached during sync, and when it was reached.
+    sync_progress: Option<(usize, Instant)>,
+    stall_restarts: u32,
+    /// Recent block heights during sync, used to smooth the sync rate.
+    sync_samples: std::collections::VecDeque<(Instant, usize)>,
+    /// Recent verification progress during sync, used to smooth the time remaining.
+    progress_samples: std::collections::VecDeque<(Instant, f64)>,
+    last_mempoolminfee: Option<f64>,
+    /// Recent confirmed fee rates, and the tip they were sampled up to.
+    recent_feerates: Option<(usize, String)>,
+    /// Consecutive failed attempts to write the stats file.
+    stats_write_failures: u32,
+    poll_interval: Duration,
+    /// The version bitcoind reported at startup, e.g. `v28.1.0`.
+    bitcoind_version: String,
+}
+
+impl SidecarState {
+    /// How long to wait before the next poll, backing off while bitcoind is slow to answer
+    /// and while writing the stats file keeps failing.
+    fn next_poll_delay(&self) -> Duration {
+        let interval = if self.under_load {
+            self.poll_interval * LOADED_POLL_MULTIPLIER
+        } else {
+            self.poll_interval
+        };
+        interval * write_backoff_multiplier(self.stats_write_failures)
+    }
+
+    /// Blocks verified since bitcoind was last started, starting over whenever it is
+    /// respawned as a new process.
+    fn blocks_this_session(&mut self, pid: Option<u32>, blocks: usize) -> usize {
+        match self.session_start {
+            Some((session_pid, start)) if session_pid == pid => blocks.saturating_sub(start),
+            _ => {
+                self.session_start = Some((pid, blocks));
+                0
+            }
+        }
+    }
+
+    /// Writes the stats file, logging a persistent failure once instead of on every poll.
+    fn write_stats(
+        &mut self,
+        config: &Mapping,
+        stats: LinearMap<Cow<'static, str>, Stat>,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(message) = self.record_stats_write(write_stats(config, stats)) {
+            eprintln!("{}", message);
+        }
+        Ok(())
+    }
+
+    /// Counts consecutive failed stats writes. Returns what to log, which is only the
+    /// first failure and the recovery from it.
+    fn record_stats_write(&mut self, res: Result<(), Box<dyn Error>>) -> Option<String> {
+        match res {
+            Ok(()) => {
+                let failures = std::mem::replace(&mut self.stats_write_failures, 0);
+                if failures > 0 {
+                    Some(format!(
+                        "Writing stats succeeded again after {} failed attempts",
+                        failures
+                    ))
+                } else {
+                    None
+                }
+            }
+            Err(e) => {
+                self.stats_write_failures += 1;
+                if self.stats_write_failures == 1 {
+                    Some(format!(
+                        "Error writing stats: {}, backing off and suppressing repeats until it succeeds",
+                        e
+                    ))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Calls bitcoind through bitcoin-cli, timing the call.
+    fn rpc(
+        &mut self,
+        config: &Mapping,
+        method: &str,
+        params: &[serde_json::Value],
+    ) -> std::io::Result<Output> {
+        self.rpc_at(config, None, method, params)
+    }
+
+    fn wallet_rpc(
+        &mut self,
+        config: &Mapping,
+        wallet: &str,
+        method: &str,
+        params: &[serde_json::Value],
+    ) -> std::io::Result<Output> {
+        self.rpc_at(config, Some(wallet), method, params)
+    }
+
+    fn rpc_at(
+        &mut self,
+        config: &Mapping,
+        wallet: Option<&str>,
+        method: &str,
+        params: &[serde_json::Value],
+    ) -> std::io::Result<Output> {
+        let started = Instant::now();
+        let mut cmd = bitcoin_cli(config);
+        if let Some(wallet) = wallet {
+            cmd.arg(format!("-rpcwallet={}", wallet));
+        }
+        let res = cmd.arg(method).args(params.iter().map(cli_arg)).output();
+        self.record_rpc(method, started.elapsed());
+        res
+    }
+
+    /// Runs several parameterless calls at once. Every bitcoin-cli call is started before
+    /// waiting on any, so one slow call doesn't hold up the others.
+    fn rpc_concurrent<const N: usize>(
+        &mut self,
+        config: &Mapping,
+        methods: [&str; N],
+    ) -> std::io::Result<[Output; N]> {
+        let started = Instant::now();
+        let children = methods
+            .iter()
+            .map(|method| {
+                bitcoin_cli(config)
+                    .arg(method)
+                    .stdout(std::process::Stdio::piped())
+                    .stderr(std::process::Stdio::piped())
+                    .spawn()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let outputs = children
+            .into_iter()
+            .map(|child| child.wait_with_output())
+            .collect::<Result<Vec<_>, _>>()?;
+        self.record_rpc(&methods.join("+"), started.elapsed());
+        Ok(<[Output; N]>::try_from(outputs).unwrap())
+    }
+
+    /// Whether bitcoind accepts the configured RPC username and password, or `None` if
+    /// it can't be reached.
+    fn rpc_auth_valid(&mut self, config: &Mapping) -> std::io::Result<Option<bool>> {
+        Ok(rpc_auth_status(&self.rpc(config, "uptime", &[])?))
+    }
+
+    /// Remembers the call if it is the slowest one of this poll.
+    fn record_rpc(&mut self, method: &str, elapsed: Duration) {
+        match &self.slowest_rpc {
+            Some((_, slowest)) if *slowest >= elapsed => (),
+            _ => self.slowest_rpc = Some((method.to_owned(), elapsed)),
+        }
+    }
+}
+
+fn config_value<'a>(config: &'a Mapping, path: &[&str]) -> Option<&'a Value> {
+    let (first, rest) = path.split_first()?;
+    rest.iter()
+        .try_fold(config.get(&Value::from(*first))?, |v, key| {
+            v.as_mapping()?.get(&Value::from(*key))
+        })
+}
+
+/// The SOCKS proxy used to reach onion peers: the configured one, or the embassy Tor proxy.
+fn onion_proxy(config: &Mapping) -> Option<String> {
+    match config_value(config, &["advanced", "peers", "onion_proxy"]).and_then(|v| v.as_str()) {
+        Some(proxy) => Some(proxy.to_owned()),
+        None => var("EMBASSY_IP").ok().map(|ip| format!("{}:9050", ip)),
+    }
+}
+
+/// Builds the `-onion` and `-proxy` args. Clearnet traffic is only proxied when a proxy is
+/// configured, or through Tor when clearnet is disabled.
+fn proxy_args(config: &Mapping) -> Result<Vec<String>, Box<dyn Error>> {
+    let onion = onion_proxy(config).ok_or("EMBASSY_IP not set")?;
+    let proxy = match config_value(config, &["advanced", "peers", "proxy"]).and_then(|v| v.as_str())
+    {
+        Some(proxy) => Some(proxy.to_owned()),
+        None if config_value(config, &["advanced", "peers", "onlyonion"])
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false) =>
+        {
+            Some(onion.clone())
+        }
+        None => None,
+    };
+    let mut args = Vec::new();
+    for (arg, addr) in [("onion", Some(onion)), ("proxy", proxy)] {
+        if let Some(addr) = addr {
+            if let Err(e) = addr.parse::<std::net::SocketAddr>() {
+                return Err(format!("Invalid {} address {:?}: {}", arg, addr, e).into());
+            }
+            args.push(format!("-{}={}", arg, addr));
+        }
+    }
+    Ok(args)
+}
+
+fn debug_log_path(config: &Mapping) -> std::path::PathBuf {
+    match config_value(config, &["advanced", "logging", "debuglogfile"]).and_then(|v| v.as_str()) {
+        Some(path) => Path::new("/root/.bitcoin").join(path),
+        None => chain_dir(config).join("debug.log"),
+    }
+}
+
+fn bitcoin_cli(config: &Mapping) -> std::process::Command {
+    let mut cmd = std::process::Command::new("bitcoin-cli");
+    cmd.arg("-conf=/root/.bitcoin/bitcoin.conf");
+    // always talk to the local node directly, regardless of any onlynet or proxy settings
+    cmd.arg("-rpcconnect=127.0.0.1");
+    cmd.arg(format!("-rpcport={}", rpc_port(config)));
+    if let Some(timeout) =
+        config_value(config, &["advanced", "stats", "cli_client_timeout"]).and_then(|v| v.as_u64())
+    {
+        cmd.arg(format!("-rpcclienttimeout={}", timeout));
+    }
+    cmd
+}
+
+/// Leaves out the port when it is the scheme's default.
+fn rpc_url(tls: bool, host: &str, port: u64) -> String {
+    match (tls, port) {
+        (true, 443) => format!("https://{}", host),
+        (false, 80) => format!("http://{}", host),
+        (true, port) => format!("https://{}:{}", host, port),
+        (false, port) => format!("http://{}:{}", host, port),
+    }
+}
+
+/// Whether RPC clients reach bitcoind through btc_rpc_proxy. Pruned nodes always need it
+/// to fetch pruned blocks from peers.
+fn rpc_proxied(config: &Mapping) -> bool {
+    config_value(config, &["advanced", "pruning", "mode"]).and_then(|v| v.as_str())
+        == Some("automatic")
+        || config_value(config, &["advanced", "proxy", "enable"])
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+}
+
+/// The port bitcoind serves RPC on locally, matching the rendered bitcoin.conf.
+fn rpc_port(config: &Mapping) -> u16 {
+    let rpc_enabled = config_value(config, &["rpc", "enable"])
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    match (rpc_enabled, rpc_proxied(config)) {
+        (true, true) => 18332,
+        (true, false) => 48332,
+        // without rpcbind, bitcoind listens on the network's default port
+        (false, _) => {
+            let chain = chain(config);
+            CHAINS
+                .iter()
+                .find(|(name, _, _)| *name == chain)
+                .map_or(48332, |(_, _, port)| *port)
+        }
+    }
+}
+
+/// The start line and headers of an HTTP message, along with the bytes they were read from.
+struct HttpHead {
+    start_line: String,
+    content_length: usize,
+    keep_alive: bool,
+    raw: Vec<u8>,
+}
+
+/// Reads the head of an HTTP message, or `None` if the connection was closed before
+/// another message began.
+fn read_http_head(reader: &mut impl std::io::BufRead) -> std::io::Result<Option<HttpHead>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let mut head = HttpHead {
+        start_line: line.trim_end().to_owned(),
+        content_length: 0,
+        keep_alive: true,
+        raw: line.clone().into_bytes(),
+    };
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        head.raw.extend_from_slice(line.as_bytes());
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                head.content_length = value.trim().parse().map_err(|_| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidData, "malformed Content-Length")
+                })?;
+            } else if name.eq_ignore_ascii_case("connection") {
+                head.keep_alive = !value.trim().eq_ignore_ascii_case("close");
+            }
+        }
+    }
+    Ok(Some(head))
+}
+
+fn http_status(head: &HttpHead) -> Option<u16> {
+    head.start_line.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// What the RPC proxy does with a request on its way to bitcoind.
+#[derive(Debug, PartialEq)]
+enum Backpressure {
+    Forward,
+    /// Answer the way bitcoind would, without adding to its backlog.
+    Shed,
+}
+
+/// Tracks when bitcoind last rejected a request because its RPC work queue was full.
+#[derive(Debug)]
+struct BackpressureGate {
+    /// How long requests are shed after bitcoind reports its work queue full.
+    cooldown: Duration,
+    saturated_at: Option<Instant>,
+}
+
+impl BackpressureGate {
+    fn decide(&self, now: Instant) -> Backpressure {
+        match self.saturated_at {
+            Some(at) if now.saturating_duration_since(at) < self.cooldown => Backpressure::Shed,
+            _ => Backpressure::Forward,
+        }
+    }
+
+    fn record(&mut self, now: Instant, status: u16, body: &[u8]) {
+        if status == 503 && work_queue_saturated(body) {
+            self.saturated_at = Some(now);
+        }
+    }
+}
+
+/// Caps how many proxy connections are forwarded to bitcoind at once.
+#[derive(Debug)]
+struct ConnectionLimit {
+    active: AtomicUsize,
+    max: usize,
+}
+
+impl ConnectionLimit {
+    fn try_acquire(self: &Arc<Self>) -> Option<ConnectionPermit> {
+        self.active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |active| {
+                if active < self.max {
+                    Some(active + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()
+            .map(|_| ConnectionPermit(self.clone()))
+    }
+}
+
+/// Holds one of a `ConnectionLimit`'s slots until dropped.
+struct ConnectionPermit(Arc<ConnectionLimit>);
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Sits between btc_rpc_proxy and bitcoind, shedding requests for a while once bitcoind
+/// reports its RPC work queue full instead of piling more onto it. Connections beyond
+/// `max_connections` are turned away the same way rather than each opening another to
+/// bitcoind.
+fn serve_backpressure(
+    listener: TcpListener,
+    upstream: SocketAddr,
+    gate: Arc<Mutex<BackpressureGate>>,
+    max_connections: usize,
+) {
+    let limit = Arc::new(ConnectionLimit {
+        active: AtomicUsize::new(0),
+        max: max_connections,
+    });
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => match limit.try_acquire() {
+                Some(permit) => {
+                    let gate = gate.clone();
+                    std::thread::spawn(move || {
+                        let _permit = permit;
+                        if let Err(e) = forward_requests(&stream, upstream, &gate) {
+                            eprintln!("Error forwarding RPC request: {}", e);
+                        }
+                    });
+                }
+                None => {
+                    if let Err(e) = reject_connection(&stream) {
+                        eprintln!("Error rejecting RPC proxy connection: {}", e);
+                    }
+                }
+            },
+            Err(e) => eprintln!("Error accepting RPC proxy connection: {}", e),
+        }
+    }
+}
+
+/// As many connections as bitcoind takes requests from before it reports its work queue
+/// full: one per RPC thread plus the queue.
+fn backpressure_max_connections(config: &Mapping) -> usize {
+    let threads = config_value(config, &["rpc", "advanced", "threads"])
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_RPC_THREADS);
+    let workqueue = config_value(config, &["rpc", "advanced", "workqueue"])
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_RPC_WORKQUEUE);
+    (threads + workqueue) as usize
+}
+
+/// Answers a connection over the limit with a single 503 and closes it. The request is
+/// read first so that closing with it unread doesn't reset the connection before the
+/// client sees the response.
+fn reject_connection(client: &TcpStream) -> std::io::Result<()> {
+    use std::io::Read;
+
+    client.set_read_timeout(Some(Duration::from_millis(500)))?;
+    let mut reader = std::io::BufReader::new(client);
+    if let Some(request) = read_http_head(&mut reader)? {
+        reader.read_exact(&mut vec![0; request.content_length])?;
+    }
+    write_work_queue_full(client, true)
+}
+
+fn write_work_queue_full(mut writer: impl Write, close: bool) -> std::io::Result<()> {
+    const BODY: &str = "Work queue depth exceeded";
+    write!(
+        writer,
+        "HTTP/1.1 503 Service Unavailable\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n{}\r\n{}",
+        BODY.len(),
+        if close { "Connection: close\r\n" } else { "" },
+        BODY
+    )
+}
+
+/// Forwards a client's requests over a single kept-alive connection to bitcoind.
+fn forward_requests(
+    client: &TcpStream,
+    upstream: SocketAddr,
+    gate: &Mutex<BackpressureGate>,
+) -> std::io::Result<()> {
+    use std::io::Read;
+
+    let mut reader = std::io::BufReader::new(client);
+    let mut writer = client;
+    let mut upstream_conn: Option<TcpStream> = None;
+    while let Some(mut request) = read_http_head(&mut reader)? {
+        let mut body = vec![0; request.content_length];
+        reader.read_exact(&mut body)?;
+        request.raw.extend_from_slice(&body);
+        let decision = gate.lock().unwrap().decide(Instant::now());
+        match decision {
+            Backpressure::Shed => write_work_queue_full(writer, false)?,
+            Backpressure::Forward => {
+                let reused = upstream_conn.is_some();
+                let conn = match upstream_conn.take() {
+                    Some(conn) => conn,
+                    None => TcpStream::connect(upstream)?,
+                };
+                let (conn, (mut response, body)) = match exchange(&conn, &request.raw) {
+                    Ok(Some(response)) => (conn, response),
+                    // bitcoind closed the idle connection, so the request never reached it
+                    Ok(None) | Err(_) if reused => {
+                        let conn = TcpStream::connect(upstream)?;
+                        let response = exchange(&conn, &request.raw)?
+                            .ok_or(std::io::ErrorKind::UnexpectedEof)?;
+                        (conn, response)
+                    }
+                    Ok(None) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                    Err(e) => return Err(e),
+                };
+                if let Some(status) = http_status(&response) {
+                    gate.lock().unwrap().record(Instant::now(), status, &body);
+                }
+                if response.keep_alive {
+                    upstream_conn = Some(conn);
+                }
+                response.raw.extend_from_slice(&body);
+                writer.write_all(&response.raw)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sends a request to bitcoind and reads its response, or `None` if the connection was
+/// closed before a response started.
+fn exchange(conn: &TcpStream, request: &[u8]) -> std::io::Result<Option<(HttpHead, Vec<u8>)>> {
+    use std::io::Read;
+
+    (&*conn).write_all(request)?;
+    let mut reader = std::io::BufReader::new(conn);
+    let response = match read_http_head(&mut reader)? {
+        Some(response) => response,
+        None => return Ok(None),
+    };
+    let mut body = vec![0; response.content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Some((response, body)))
+}
+
+/// bitcoin-cli reads the credentials from bitcoin.conf, which is rendered from the
+/// same config, and reports rejected ones distinctly from an unreachable server.
+fn rpc_auth_status(res: &Output) -> Option<bool> {
+    if res.status.success() {
+        Some(true)
+    } else if String::from_utf8_lossy(&res.stderr).contains("Authorization failed") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+fn cli_arg(param: &serde_json::Value) -> String {
+    match param {
+        serde_json::Value::String(s) => s.clone(),
+        param => param.to_string(),
+    }
+}
+
+fn sidecar(config: &Mapping, addr: &str, state: &mut SidecarState) -> Result<(), Box<dyn Error>> {
     let mut stats = LinearMap::new();
+    if let Some(now) = unix_now() {
+        stats.insert(
+            Cow::from("Stats Last Updated"),
+            Stat {
+                value_type: "string",
+                value: human_readable_timestamp(now),
+                description: Some(Cow::from(
+                    "When these stats were updated (UTC). Flagged as stale when the manager has stopped updating them for several poll intervals",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+    }
     if let (Some(user), Some(pass)) = (
         config
             .get(&Value::String("rpc".to_owned()))
@@ -134,17 +893,36 @@ fn sidecar(config: &Mapping, addr: &str) -> Result<(), Box<dyn Error>> {
             },
         );
         let addr_local = format!("{}local", addr.strip_suffix("onion").unwrap());
+        let lan_port = config_value(config, &["rpc", "advanced", "lan_port"])
+            .and_then(|v| v.as_u64())
+            .unwrap_or(443);
+        let lan_tls = config_value(config, &["rpc", "advanced", "lan_tls"])
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
         stats.insert(
             Cow::from("LAN Quick Connect"),
             Stat {
                 value_type: "string",
-                value: format!("btcstandup://{}:{}@{}:443", user, pass, addr_local),
+                value: format!("btcstandup://{}:{}@{}:{}", user, pass, addr_local, lan_port),
                 description: Some(Cow::from("Bitcoin-Standup LAN Quick Connect URL")),
                 copyable: true,
                 qr: true,
                 masked: true,
             },
         );
+        stats.insert(
+            Cow::from("LAN RPC URL"),
+            Stat {
+                value_type: "string",
+                value: rpc_url(lan_tls, &addr_local, lan_port),
+                description: Some(Cow::from(
+                    "The address to reach Bitcoin RPC at on the local network",
+                )),
+                copyable: true,
+                qr: false,
+                masked: false,
+            },
+        );
         stats.insert(
             Cow::from("RPC Username"),
             Stat {
@@ -168,25 +946,83 @@ fn sidecar(config: &Mapping, addr: &str) -> Result<(), Box<dyn Error>> {
             },
         );
     }
-    let info_res = std::process::Command::new("bitcoin-cli")
-        .arg("-conf=/root/.bitcoin/bitcoin.conf")
-        .arg("getblockchaininfo")
-        .output()?;
-    if info_res.status.success() {
-        let info: ChainInfo = serde_json::from_slice(&info_res.stdout)?;
+    stats.insert(
+        Cow::from("Shutdown Timeout"),
+        Stat {
+            value_type: "string",
+            value: format!("{}s", SHUTDOWN_TIMEOUT_SECS.load(Ordering::SeqCst)),
+            description: Some(Cow::from(
+                "How long Bitcoin Core is given to shut down cleanly before it is forcefully stopped",
+            )),
+            copyable: false,
+            qr: false,
+            masked: false,
+        },
+    );
+    if !node_running() {
+        // bitcoind is gone or on its way out, so skip RPC calls rather than logging errors
         stats.insert(
-            Cow::from("Block Height"),
+            Cow::from("Node Status"),
             Stat {
                 value_type: "string",
-                value: format!("{}", info.headers),
-                description: Some(Cow::from("The current block height for the network")),
+                value: "Stopped".to_owned(),
+                description: Some(Cow::from("Bitcoin Core is not running")),
                 copyable: false,
                 qr: false,
                 masked: false,
             },
         );
-        stats.insert(
-            Cow::from("Synced Block Height"),
+        return state.write_stats(config, stats);
+    }
+    state.slowest_rpc = None;
+    let mut chain_tip = None;
+    let mut initial_sync = true;
+    let mut node_warnings = Vec::new();
+    let started = Instant::now();
+    let [info_res, network_info_res] =
+        state.rpc_concurrent(config, ["getblockchaininfo", "getnetworkinfo"])?;
+    state.under_load = config_value(config, &["advanced", "stats", "adaptive_polling"])
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+        && under_load(started.elapsed());
+    // getpeerinfo is among the most expensive calls, so the peer stats wait out the load
+    let peers = if state.under_load {
+        None
+    } else {
+        peer_info(config, state)?
+    };
+    if info_res.status.success() {
+        let info: ChainInfo = serde_json::from_slice(&info_res.stdout)?;
+        node_warnings.extend(info.warnings.clone().into_vec());
+        chain_tip = Some(info.blocks);
+        initial_sync = info.blocks < info.headers;
+        stats.insert(
+            Cow::from("Network"),
+            Stat {
+                value_type: "string",
+                value: info.chain.clone(),
+                description: Some(Cow::from("The Bitcoin network this node is running on")),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        stats.insert(
+            Cow::from("Block Height"),
+            Stat {
+                value_type: "string",
+                value: format!("{}", info.headers),
+                description: Some(Cow::from(format!(
+                    "The current block height for the {} network",
+                    info.chain
+                ))),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        stats.insert(
+            Cow::from("Synced Block Height"),
             Stat {
                 value_type: "string",
                 value: format!("{}", info.blocks),
@@ -196,155 +1032,223 @@ fn sidecar(config: &Mapping, addr: &str) -> Result<(), Box<dyn Error>> {
                 masked: false,
             },
         );
+        if info.blocks >= info.headers {
+            state.sync_progress = None;
+            state.stall_restarts = 0;
+            state.sync_samples.clear();
+            state.progress_samples.clear();
+        }
         stats.insert(
-            Cow::from("Sync Progress"),
+            Cow::from("Assume Valid"),
             Stat {
                 value_type: "string",
-                value: if info.blocks < info.headers {
-                    format!("{:.2}%", 100.0 * info.verificationprogress)
-                } else {
-                    "100%".to_owned()
-                },
+                value: effective_assumevalid(
+                    info.assumevalid.as_deref(),
+                    config_value(config, &["advanced", "assumevalid"]).and_then(|v| v.as_str()),
+                ),
                 description: Some(Cow::from(
-                    "The percentage of the blockchain that has been verified",
+                    "The block whose ancestors' scripts are assumed valid during initial sync",
                 )),
-                copyable: false,
+                copyable: true,
                 qr: false,
                 masked: false,
             },
         );
-        for (sf_name, sf_data) in info.softforks {
-            let sf_name_pretty = sf_name.to_title_case();
-            let status_desc = Some(Cow::from(format!(
-                "The Bip9 deployment status for {}",
-                sf_name_pretty
-            )));
-            let start_desc = Some(Cow::from(format!(
-                "The start time (UTC) of the Bip9 signaling period for {}",
-                sf_name_pretty
-            )));
-            let timeout_desc = Some(Cow::from(format!(
-                "The timeout time (UTC) of the Bip9 signaling period for {}",
-                sf_name_pretty
-            )));
-            match sf_data {
-                SoftFork::Buried {
-                    active: _,
-                    height: _,
-                } => continue,
-                SoftFork::Bip9 { bip9, active: _ } => {
-                    let (status, start, end, _since) = match bip9 {
-                        Bip9::Defined {
-                            start_time,
-                            timeout,
-                            since,
-                        } => {
-                            let start_time_pretty = human_readable_timestamp(start_time);
-                            let end_time_pretty = human_readable_timestamp(timeout);
-                            ("Defined", start_time_pretty, end_time_pretty, since)
-                        }
-                        Bip9::Started {
-                            start_time,
-                            timeout,
-                            since,
-                            bit: _,
-                            statistics: _,
-                        } => {
-                            let start_time_pretty = human_readable_timestamp(start_time);
-                            let end_time_pretty = human_readable_timestamp(timeout);
-                            ("Started", start_time_pretty, end_time_pretty, since)
-                        }
-                        Bip9::LockedIn {
-                            start_time,
-                            timeout,
-                            since,
-                        } => {
-                            let start_time_pretty = human_readable_timestamp(start_time);
-                            let end_time_pretty = human_readable_timestamp(timeout);
-                            ("Locked In", start_time_pretty, end_time_pretty, since)
-                        }
-                        Bip9::Active {
-                            start_time,
-                            timeout,
-                            since,
-                        } => {
-                            // stop showing soft fork info when it's been active for ~12 weeks
-                            if info.blocks >= since + 12096 {
-                                continue;
-                            }
-                            let start_time_pretty = human_readable_timestamp(start_time);
-                            let end_time_pretty = human_readable_timestamp(timeout);
-                            ("Active", start_time_pretty, end_time_pretty, since)
-                        }
-                        Bip9::Failed {
-                            start_time,
-                            timeout,
-                            since,
-                        } => {
-                            let start_time_pretty = human_readable_timestamp(start_time);
-                            let end_time_pretty = human_readable_timestamp(timeout);
-                            ("Active", start_time_pretty, end_time_pretty, since)
-                        }
-                    };
-                    stats.insert(
-                        Cow::from(format!("{} Status", sf_name_pretty)),
-                        Stat {
-                            value_type: "string",
-                            value: status.to_owned(),
-                            description: status_desc,
-                            copyable: false,
-                            qr: false,
-                            masked: false,
-                        },
-                    );
-                    stats.insert(
-                        Cow::from(format!("{} Start Time", sf_name_pretty)),
-                        Stat {
-                            value_type: "string",
-                            value: start,
-                            description: start_desc,
-                            copyable: false,
-                            qr: false,
-                            masked: false,
-                        },
-                    );
-                    stats.insert(
-                        Cow::from(format!("{} Timeout", sf_name_pretty)),
-                        Stat {
-                            value_type: "string",
-                            value: end,
-                            description: timeout_desc,
-                            copyable: false,
-                            qr: false,
-                            masked: false,
-                        },
+        if info.blocks < info.headers {
+            let now = Instant::now();
+            state.sync_samples.push_back((now, info.blocks));
+            while let Some((t, _)) = state.sync_samples.front() {
+                if now.duration_since(*t) <= SYNC_RATE_WINDOW {
+                    break;
+                }
+                state.sync_samples.pop_front();
+            }
+            state
+                .progress_samples
+                .push_back((now, info.verificationprogress));
+            while let Some((t, _)) = state.progress_samples.front() {
+                if now.duration_since(*t) <= SYNC_RATE_WINDOW {
+                    break;
+                }
+                state.progress_samples.pop_front();
+            }
+            if let Some(remaining) = remaining_sync_secs(&state.progress_samples) {
+                stats.insert(
+                    Cow::from("Estimated Time Remaining"),
+                    Stat {
+                        value_type: "string",
+                        value: format!("~{}", human_readable_duration(remaining)),
+                        description: Some(Cow::from(
+                            "How long the initial sync should take to finish at the recent verification rate",
+                        )),
+                        copyable: false,
+                        qr: false,
+                        masked: false,
+                    },
+                );
+            }
+            if let Some(completion) = smoothed_sync_rate(&state.sync_samples).and_then(|rate| {
+                estimated_completion(
+                    std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .ok()?
+                        .as_secs(),
+                    info.headers - info.blocks,
+                    rate,
+                )
+            }) {
+                stats.insert(
+                    Cow::from("Estimated Completion"),
+                    Stat {
+                        value_type: "string",
+                        value: human_readable_timestamp(completion),
+                        description: Some(Cow::from(
+                            "When the initial sync is expected to finish at the recent sync rate (UTC)",
+                        )),
+                        copyable: false,
+                        qr: false,
+                        masked: false,
+                    },
+                );
+            }
+            match state.sync_progress {
+                Some((height, since)) if height == info.blocks => {
+                    let stalled_for = now.duration_since(since);
+                    let threshold = Duration::from_secs(
+                        config_value(config, &["advanced", "recovery", "stall_minutes"])
+                            .and_then(|v| v.as_u64())
+                            .unwrap_or(DEFAULT_STALL_MINUTES)
+                            * 60,
                     );
-                    if let Bip9::Started {
-                        statistics,
-                        start_time: _,
-                        timeout: _,
-                        since: _,
-                        bit: _,
-                    } = bip9
-                    {
+                    if stalled_for >= threshold {
                         stats.insert(
-                            Cow::from(format!("{} Signal Percentage", sf_name_pretty)),
+                            Cow::from("Sync Stalled"),
                             Stat {
                                 value_type: "string",
-                                value: format!(
-                                    "{:.2}%",
-                                    100.0 * (statistics.count as f64) / (statistics.elapsed as f64)
-                                ),
-                                description: Some(Cow::from(format!("Percentage of the blocks in the current signaling window that are signaling for the activation of {}", sf_name_pretty))),
+                                value: human_readable_duration(stalled_for.as_secs()),
+                                description: Some(Cow::from(
+                                    "How long the node has been syncing without verifying a new block",
+                                )),
                                 copyable: false,
                                 qr: false,
                                 masked: false,
                             },
                         );
                     }
+                    if config_value(config, &["advanced", "recovery", "auto_restart_on_stall"])
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false)
+                        && should_restart_on_stall(stalled_for, threshold, state.stall_restarts)
+                    {
+                        state.stall_restarts += 1;
+                        state.sync_progress = None;
+                        eprintln!(
+                            "Sync stalled at block {} for {}, restarting bitcoind ({}/{})",
+                            info.blocks,
+                            human_readable_duration(stalled_for.as_secs()),
+                            state.stall_restarts,
+                            MAX_STALL_RESTARTS
+                        );
+                        request_restart();
+                    }
                 }
+                _ => state.sync_progress = Some((info.blocks, now)),
+            }
+            if let Some(lag) = header_lag(config, state, &info)? {
+                stats.insert(
+                    Cow::from("Behind By"),
+                    Stat {
+                        value_type: "string",
+                        value: format!("~{}", human_readable_duration(lag)),
+                        description: Some(Cow::from(
+                            "How far the verified chain trails the best known header in block time",
+                        )),
+                        copyable: false,
+                        qr: false,
+                        masked: false,
+                    },
+                );
             }
         }
+        let child_pid = *CHILD_PID.lock().unwrap();
+        stats.insert(
+            Cow::from("Blocks This Session"),
+            Stat {
+                value_type: "string",
+                value: format!("{}", state.blocks_this_session(child_pid, info.blocks)),
+                description: Some(Cow::from(
+                    "The number of blocks verified since Bitcoin Core was last started",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        let headers_remaining = peers
+            .as_ref()
+            .and_then(|peers| headers_remaining(peers, &info));
+        stats.insert(
+            Cow::from("Sync Phase"),
+            Stat {
+                value_type: "string",
+                value: sync_phase(&info, headers_remaining).to_owned(),
+                description: Some(Cow::from(
+                    "Whether the node is still fetching block headers or downloading the blocks themselves",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        if let Some(remaining) = headers_remaining.filter(|remaining| *remaining > 0) {
+            stats.insert(
+                Cow::from("Headers Remaining"),
+                Stat {
+                    value_type: "string",
+                    value: format!("~{}", remaining),
+                    description: Some(Cow::from(
+                        "The approximate number of block headers left to fetch, based on the heights reported by peers",
+                    )),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+        }
+        stats.insert(
+            Cow::from("Sync Progress"),
+            Stat {
+                value_type: "string",
+                value: sync_progress(&info),
+                description: Some(Cow::from(
+                    "The percentage of the blockchain that has been verified",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        stats.insert(
+            Cow::from("Download Progress"),
+            Stat {
+                value_type: "string",
+                value: download_progress(&info),
+                description: Some(Cow::from(
+                    "The percentage of known block headers whose blocks have been downloaded and connected",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        let softforks = match state.rpc(config, "getdeploymentinfo", &[])? {
+            res if res.status.success() => {
+                serde_json::from_slice::<DeploymentInfo>(&res.stdout)?.deployments
+            }
+            // older versions of bitcoind only report deployments in getblockchaininfo
+            _ => info.softforks,
+        };
+        insert_softfork_stats(&mut stats, softforks, info.blocks);
         stats.insert(
             Cow::from("Disk Usage"),
             Stat {
@@ -370,8 +1274,41 @@ fn sidecar(config: &Mapping, addr: &str) -> Result<(), Box<dyn Error>> {
                     masked: false,
                 },
             );
+            stats.insert(
+                Cow::from("Retained Blocks"),
+                Stat {
+                    value_type: "string",
+                    value: retained_blocks(info.blocks, info.pruneheight),
+                    description: Some(Cow::from(
+                        "The number of recent blocks still stored on disk and available to serve",
+                    )),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+        } else if let Some(prune_target) =
+            config_value(config, &["advanced", "pruning", "size"]).and_then(|v| v.as_u64())
+        {
+            if let Some(stable_height) =
+                prune_stable_height(info.size_on_disk, info.blocks, prune_target * 1024 * 1024)
+            {
+                stats.insert(
+                    Cow::from("Pruning Stabilizes"),
+                    Stat {
+                        value_type: "string",
+                        value: format!("~block {}", stable_height),
+                        description: Some(Cow::from(
+                            "The estimated block height at which pruning starts and disk usage stops growing",
+                        )),
+                        copyable: false,
+                        qr: false,
+                        masked: false,
+                    },
+                );
+            }
         }
-    } else if info_res.status.code() == Some(28) {
+    } else if info_res.status.code() == Some(28) || work_queue_saturated(&info_res.stderr) {
         return Ok(());
     } else {
         eprintln!(
@@ -379,12 +1316,51 @@ fn sidecar(config: &Mapping, addr: &str) -> Result<(), Box<dyn Error>> {
             std::str::from_utf8(&info_res.stderr).unwrap_or("UNKNOWN ERROR")
         );
     }
-    let info_res = std::process::Command::new("bitcoin-cli")
-        .arg("-conf=/root/.bitcoin/bitcoin.conf")
-        .arg("getnetworkinfo")
-        .output()?;
+    if let Some(valid) = state.rpc_auth_valid(config)? {
+        stats.insert(
+            Cow::from("RPC Auth"),
+            Stat {
+                value_type: "string",
+                value: if valid { "Valid" } else { "Invalid" }.to_owned(),
+                description: Some(Cow::from(
+                    "Whether Bitcoin Core accepts the RPC username and password shown here",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+    }
+    let info_res = state.rpc(config, "uptime", &[])?;
+    if info_res.status.success() {
+        let uptime: u64 = serde_json::from_slice(&info_res.stdout)?;
+        stats.insert(
+            Cow::from("Uptime"),
+            Stat {
+                value_type: "string",
+                value: human_readable_duration(uptime),
+                description: Some(Cow::from("How long Bitcoin Core has been running")),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+    } else if info_res.status.code() == Some(28) || work_queue_saturated(&info_res.stderr) {
+        return Ok(());
+    } else {
+        eprintln!(
+            "Error updating uptime: {}",
+            std::str::from_utf8(&info_res.stderr).unwrap_or("UNKNOWN ERROR")
+        );
+    }
+    let info_res = network_info_res;
     if info_res.status.success() {
         let info: NetworkInfo = serde_json::from_slice(&info_res.stdout)?;
+        for warning in info.warnings.clone().into_vec() {
+            if !node_warnings.contains(&warning) {
+                node_warnings.push(warning);
+            }
+        }
         stats.insert(
             Cow::from("Connections"),
             Stat {
@@ -396,7 +1372,116 @@ fn sidecar(config: &Mapping, addr: &str) -> Result<(), Box<dyn Error>> {
                 masked: false,
             },
         );
-    } else if info_res.status.code() == Some(28) {
+        stats.insert(
+            Cow::from("Onion Service"),
+            Stat {
+                value_type: "string",
+                value: onion_service_status(&info.localaddresses),
+                description: Some(Cow::from(
+                    "The version of the onion addresses this node advertises to peers",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        if let Some(peers) = &peers {
+            stats.insert(
+                Cow::from("Block-Relay-Only Peers"),
+                Stat {
+                    value_type: "string",
+                    value: block_relay_only_status(peers),
+                    description: Some(Cow::from(
+                        "Outbound connections that only relay blocks, which help protect the node against eclipse attacks",
+                    )),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+            stats.insert(
+                Cow::from("Peers by Network"),
+                Stat {
+                    value_type: "string",
+                    value: peers_by_network(peers),
+                    description: Some(Cow::from(
+                        "The connected peers by the network they are reached over, such as Tor (onion), clearnet (ipv4/ipv6) or I2P",
+                    )),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+        }
+        stats.insert(
+            Cow::from("Peer Time Offset"),
+            Stat {
+                value_type: "string",
+                value: if time_offset_unsafe(info.timeoffset) {
+                    format!("{:+}s - check your system clock", info.timeoffset)
+                } else {
+                    format!("{:+}s", info.timeoffset)
+                },
+                description: Some(Cow::from(
+                    "How far this node's clock is from the clocks of its peers",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        stats.insert(Cow::from("User Agent"), user_agent_stat(&info));
+        stats.insert(
+            Cow::from("Serving Historical Blocks"),
+            Stat {
+                value_type: "string",
+                value: historical_blocks_status(&info.localservicesnames).to_owned(),
+                description: Some(Cow::from(
+                    "Whether this node can serve the full block history to peers bootstrapping a new node",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        stats.insert(
+            Cow::from("Discoverable"),
+            Stat {
+                value_type: "string",
+                value: discoverability(
+                    listen_enabled(config),
+                    info.localaddresses.len(),
+                    &info.localservicesnames,
+                )
+                .to_owned(),
+                description: Some(Cow::from(
+                    "Whether network crawlers are likely to find and count this node: it must accept connections, advertise an address and offer blocks to peers",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        if listen_enabled(config) {
+            stats.insert(
+                Cow::from("Inbound"),
+                Stat {
+                    value_type: "string",
+                    value: if inbound_reachable(&info) {
+                        "Reachable".to_owned()
+                    } else {
+                        "Not Reachable".to_owned()
+                    },
+                    description: Some(Cow::from(
+                        "Whether other nodes are able to open connections to this node",
+                    )),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+        }
+    } else if info_res.status.code() == Some(28) || work_queue_saturated(&info_res.stderr) {
         return Ok(());
     } else {
         eprintln!(
@@ -404,160 +1489,4767 @@ fn sidecar(config: &Mapping, addr: &str) -> Result<(), Box<dyn Error>> {
             std::str::from_utf8(&info_res.stderr).unwrap_or("UNKNOWN ERROR")
         );
     }
-    serde_yaml::to_writer(
-        std::fs::File::create("/root/.bitcoin/start9/.stats.yaml.tmp")?,
-        &Stats {
-            version: 2,
-            data: stats,
-        },
-    )?;
-    std::fs::rename(
-        "/root/.bitcoin/start9/.stats.yaml.tmp",
-        "/root/.bitcoin/start9/stats.yaml",
-    )?;
-    Ok(())
-}
-
-fn inner_main(reindex: bool, reindex_chainstate: bool) -> Result<(), Box<dyn Error>> {
-    while !Path::new("/root/.bitcoin/start9/config.yaml").exists() {
-        std::thread::sleep(std::time::Duration::from_secs(1));
-    }
-    let config: Mapping =
-        serde_yaml::from_reader(std::fs::File::open("/root/.bitcoin/start9/config.yaml")?)?;
-    let sidecar_poll_interval = std::time::Duration::from_secs(5);
-    let peer_addr = var("PEER_TOR_ADDRESS")?;
+    if !node_warnings.is_empty() {
+        stats.insert(
+            Cow::from("Node Warnings"),
+            Stat {
+                value_type: "string",
+                value: node_warnings.join("; "),
+                description: Some(Cow::from(
+                    "Warnings reported by Bitcoin Core, such as unknown block versions or a large reorganization",
+                )),
+                copyable: true,
+                qr: false,
+                masked: false,
+            },
+        );
+    }
+    let chainstates = if !version_at_least(&state.bitcoind_version, GETCHAINSTATES_MIN_VERSION) {
+        None
+    } else {
+        let info_res = state.rpc(config, "getchainstates", &[])?;
+        if info_res.status.success() {
+            Some(serde_json::from_slice::<ChainStates>(&info_res.stdout)?)
+        } else if info_res.status.code() == Some(28) || work_queue_saturated(&info_res.stderr) {
+            return Ok(());
+        } else {
+            eprintln!(
+                "Error updating chainstate info: {}",
+                std::str::from_utf8(&info_res.stderr).unwrap_or("UNKNOWN ERROR")
+            );
+            None
+        }
+    };
+    if let Some(status) = chainstates.as_ref().and_then(snapshot_status) {
+        stats.insert(
+            Cow::from("AssumeUTXO Snapshot"),
+            Stat {
+                value_type: "string",
+                value: status.to_owned(),
+                description: Some(Cow::from(
+                    "Whether the loaded UTXO snapshot has been fully validated in the background",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+    }
+    stats.insert(
+        Cow::from("Verification Mode"),
+        Stat {
+            value_type: "string",
+            value: verification_mode(
+                config_value(config, &["advanced", "assumevalid"]).and_then(|v| v.as_str()),
+                chainstates
+                    .iter()
+                    .flat_map(|info| &info.chainstates)
+                    .any(|c| c.snapshot_blockhash.is_some()),
+            ),
+            description: Some(Cow::from(
+                "Which validation shortcuts, if any, this node is trusting",
+            )),
+            copyable: false,
+            qr: false,
+            masked: false,
+        },
+    );
+    let info_res = state.rpc(config, "getnettotals", &[])?;
+    if info_res.status.success() {
+        let info: NetTotals = serde_json::from_slice(&info_res.stdout)?;
+        if let Some((recv_rate, sent_rate)) = state
+            .last_net_totals
+            .as_ref()
+            .and_then(|last| bandwidth_rate(last, &info))
+        {
+            stats.insert(
+                Cow::from("Bandwidth"),
+                Stat {
+                    value_type: "string",
+                    value: format!(
+                        "↓ {:.2} KiB/s ↑ {:.2} KiB/s",
+                        recv_rate / 1024_f64,
+                        sent_rate / 1024_f64
+                    ),
+                    description: Some(Cow::from(
+                        "The current download and upload rate to and from peers",
+                    )),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+        }
+        for (name, bytes, direction) in &[
+            ("Data Downloaded", info.totalbytesrecv, "received from"),
+            ("Data Uploaded", info.totalbytessent, "sent to"),
+        ] {
+            stats.insert(
+                Cow::from(*name),
+                Stat {
+                    value_type: "string",
+                    value: format!("{:.2} GiB", *bytes as f64 / 1024_f64.powf(3_f64)),
+                    description: Some(Cow::from(format!(
+                        "Total data {} peers since Bitcoin Core started",
+                        direction
+                    ))),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+        }
+        stats.insert(
+            Cow::from("Serve Ratio"),
+            Stat {
+                value_type: "string",
+                value: serve_ratio(info.totalbytessent, info.totalbytesrecv),
+                description: Some(Cow::from(
+                    "Bytes uploaded to peers per byte downloaded from them since Bitcoin Core started",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        if let Some(upload_target) = format_upload_target(&info.uploadtarget) {
+            stats.insert(
+                Cow::from("Upload Target"),
+                Stat {
+                    value_type: "string",
+                    value: upload_target,
+                    description: Some(Cow::from(format!(
+                        "Upload allowance remaining in the current {} cycle",
+                        human_readable_duration(info.uploadtarget.timeframe)
+                    ))),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+        }
+        state.last_net_totals = Some(info);
+    } else if info_res.status.code() == Some(28) || work_queue_saturated(&info_res.stderr) {
+        return Ok(());
+    } else {
+        eprintln!(
+            "Error updating network totals: {}",
+            std::str::from_utf8(&info_res.stderr).unwrap_or("UNKNOWN ERROR")
+        );
+    }
+    let blockfilterindex = config_value(config, &["advanced", "blockfilters", "blockfilterindex"])
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let peerblockfilters = config_value(config, &["advanced", "blockfilters", "peerblockfilters"])
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let txindex = config_value(config, &["txindex"])
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let coinstatsindex = config_value(config, &["coinstatsindex"])
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if txindex || coinstatsindex || blockfilterindex || peerblockfilters {
+        let info_res = state.rpc(config, "getindexinfo", &[])?;
+        if info_res.status.success() {
+            let info: LinearMap<String, IndexStatus> = serde_json::from_slice(&info_res.stdout)?;
+            if blockfilterindex || peerblockfilters {
+                stats.insert(
+                    Cow::from("Light Client Serving"),
+                    Stat {
+                        value_type: "string",
+                        value: light_client_readiness(
+                            blockfilterindex,
+                            peerblockfilters,
+                            info.get("basic block filter index").map(|i| i.synced),
+                        )
+                        .to_owned(),
+                        description: Some(Cow::from(
+                            "Whether compact block filters (BIP157/158) are ready for light clients",
+                        )),
+                        copyable: false,
+                        qr: false,
+                        masked: false,
+                    },
+                );
+            }
+            for (index, name) in &[
+                ("txindex", "Transaction Index"),
+                ("coinstatsindex", "Coinstatsindex"),
+                ("basic block filter index", "Block Filter Index"),
+            ] {
+                if let (Some(status), Some(tip)) = (info.get(*index), chain_tip) {
+                    stats.insert(
+                        Cow::from(*name),
+                        Stat {
+                            value_type: "string",
+                            value: index_lag_status(status, tip),
+                            description: Some(Cow::from(format!(
+                                "Whether the {} has caught up with the chain tip",
+                                index
+                            ))),
+                            copyable: false,
+                            qr: false,
+                            masked: false,
+                        },
+                    );
+                }
+            }
+        } else if info_res.status.code() == Some(28) || work_queue_saturated(&info_res.stderr) {
+            return Ok(());
+        } else {
+            eprintln!(
+                "Error updating index info: {}",
+                std::str::from_utf8(&info_res.stderr).unwrap_or("UNKNOWN ERROR")
+            );
+        }
+    }
+    if !state.under_load && coinstatsindex {
+        // muhash stats are served from coinstatsindex rather than by scanning the UTXO set
+        let info_res = state.rpc(config, "gettxoutsetinfo", &["muhash".into()])?;
+        if info_res.status.success() {
+            let info: TxOutSetInfo = serde_json::from_slice(&info_res.stdout)?;
+            stats.insert(
+                Cow::from("UTXO Set"),
+                Stat {
+                    value_type: "string",
+                    value: format_utxo_set(
+                        &info,
+                        dir_size(chain_dir(config).join("chainstate")).ok(),
+                    ),
+                    description: Some(Cow::from(
+                        "The number of unspent transaction outputs and their size on disk",
+                    )),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+            if let Some(unspendable) = info.total_unspendable_amount {
+                stats.insert(
+                    Cow::from("Unspendable Coins"),
+                    Stat {
+                        value_type: "string",
+                        value: format!("{:.8} BTC", unspendable),
+                        description: Some(Cow::from(
+                            "Coins that can never be spent: burned outputs, unclaimed block rewards, the genesis block reward and BIP30 duplicates",
+                        )),
+                        copyable: false,
+                        qr: false,
+                        masked: false,
+                    },
+                );
+            }
+        } else if info_res.status.code() == Some(28) || work_queue_saturated(&info_res.stderr) {
+            return Ok(());
+        } else if !String::from_utf8_lossy(&info_res.stderr).contains("still syncing") {
+            eprintln!(
+                "Error updating UTXO set info: {}",
+                std::str::from_utf8(&info_res.stderr).unwrap_or("UNKNOWN ERROR")
+            );
+        }
+    }
+    let info_res = state.rpc(config, "getmempoolinfo", &[])?;
+    if info_res.status.success() {
+        let info: MempoolInfo = serde_json::from_slice(&info_res.stdout)?;
+        stats.insert(
+            Cow::from("Mempool"),
+            Stat {
+                value_type: "string",
+                value: mempool_fullness(info.usage, info.maxmempool),
+                description: Some(Cow::from(
+                    "How much of the configured maximum mempool size is in use",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        if mempool_evicting(&info, state.last_mempoolminfee) {
+            stats.insert(
+                Cow::from("Mempool Evicting Low-Fee Txns"),
+                Stat {
+                    value_type: "string",
+                    value: format!(
+                        "Yes - below {:.2} sat/vB is not relayed",
+                        info.mempoolminfee * 1e5
+                    ),
+                    description: Some(Cow::from(
+                        "The mempool is full and dropping its lowest fee transactions, which is why low-fee transactions are not relaying",
+                    )),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+        }
+        state.last_mempoolminfee = Some(info.mempoolminfee);
+        if info.size > 0 {
+            stats.insert(
+                Cow::from("Mempool Transactions"),
+                Stat {
+                    value_type: "string",
+                    value: format!("{} ({:.2} MvB)", info.size, info.bytes as f64 / 1e6),
+                    description: Some(Cow::from(
+                        "The number of unconfirmed transactions in the mempool and their total virtual size",
+                    )),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+            stats.insert(
+                Cow::from("Mempool Memory Usage"),
+                Stat {
+                    value_type: "string",
+                    value: format!("{:.2} MiB", info.usage as f64 / 1024_f64.powf(2_f64)),
+                    description: Some(Cow::from("The memory used by the mempool")),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+            stats.insert(
+                Cow::from("Mempool Minimum Fee"),
+                Stat {
+                    value_type: "string",
+                    // BTC/kvB to sat/vB
+                    value: format!("{:.2} sat/vB", info.mempoolminfee * 1e5),
+                    description: Some(Cow::from(
+                        "The lowest fee rate a transaction needs to be accepted into the mempool",
+                    )),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+        }
+    } else if info_res.status.code() == Some(28) || work_queue_saturated(&info_res.stderr) {
+        return Ok(());
+    } else {
+        eprintln!(
+            "Error updating mempool info: {}",
+            std::str::from_utf8(&info_res.stderr).unwrap_or("UNKNOWN ERROR")
+        );
+    }
+    for (target, name) in &[
+        (1, "Fee Next Block"),
+        (6, "Fee ~1 Hour"),
+        (144, "Fee ~1 Day"),
+    ] {
+        let info_res = state.rpc(config, "estimatesmartfee", &[(*target).into()])?;
+        if info_res.status.success() {
+            let info: SmartFee = serde_json::from_slice(&info_res.stdout)?;
+            stats.insert(
+                Cow::from(*name),
+                Stat {
+                    value_type: "string",
+                    value: format_smart_fee(&info),
+                    description: Some(Cow::from(format!(
+                        "The estimated fee rate for confirmation within {} blocks",
+                        target
+                    ))),
+                    copyable: true,
+                    qr: false,
+                    masked: false,
+                },
+            );
+        } else if info_res.status.code() == Some(28) || work_queue_saturated(&info_res.stderr) {
+            return Ok(());
+        } else {
+            eprintln!(
+                "Error updating fee estimates: {}",
+                std::str::from_utf8(&info_res.stderr).unwrap_or("UNKNOWN ERROR")
+            );
+        }
+    }
+    if let (Some(tip), false, false) = (chain_tip, initial_sync, state.under_load) {
+        // recent blocks are only worth sampling once the node has caught up to them, and
+        // only again once a new block arrives
+        let feerates = match &state.recent_feerates {
+            Some((sampled_at, feerates)) if *sampled_at == tip => Some(feerates.clone()),
+            _ => {
+                let mut percentiles = Vec::new();
+                for height in tip.saturating_sub(RECENT_FEE_BLOCKS - 1)..=tip {
+                    let info_res = state.rpc(
+                        config,
+                        "getblockstats",
+                        &[height.into(), serde_json::json!(["feerate_percentiles"])],
+                    )?;
+                    if info_res.status.success() {
+                        let info: BlockStats = serde_json::from_slice(&info_res.stdout)?;
+                        percentiles.push(info.feerate_percentiles);
+                    } else if info_res.status.code() == Some(28)
+                        || work_queue_saturated(&info_res.stderr)
+                    {
+                        return Ok(());
+                    } else {
+                        eprintln!(
+                            "Error updating block stats: {}",
+                            std::str::from_utf8(&info_res.stderr).unwrap_or("UNKNOWN ERROR")
+                        );
+                    }
+                }
+                let feerates = recent_feerates(&percentiles);
+                // a partial sample is retried on the next poll
+                state.recent_feerates = match &feerates {
+                    Some(feerates) if percentiles.len() == RECENT_FEE_BLOCKS.min(tip + 1) => {
+                        Some((tip, feerates.clone()))
+                    }
+                    _ => None,
+                };
+                feerates
+            }
+        };
+        if let Some(value) = feerates {
+            stats.insert(
+                Cow::from("Recent Confirmed Feerates"),
+                Stat {
+                    value_type: "string",
+                    value,
+                    description: Some(Cow::from(format!(
+                        "The 10th, 50th and 90th percentile fee rates paid in the last {} blocks",
+                        RECENT_FEE_BLOCKS
+                    ))),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+        }
+    }
+    if config_value(config, &["wallet", "enable"]).and_then(|v| v.as_bool()) == Some(true) {
+        let info_res = state.rpc(config, "listwallets", &[])?;
+        if info_res.status.success() {
+            let wallets: Vec<String> = serde_json::from_slice(&info_res.stdout)?;
+            let mut infos = Vec::with_capacity(wallets.len());
+            for wallet in &wallets {
+                let info_res = state.wallet_rpc(config, wallet, "getwalletinfo", &[])?;
+                if info_res.status.success() {
+                    infos.push(serde_json::from_slice(&info_res.stdout)?);
+                } else if info_res.status.code() == Some(28)
+                    || work_queue_saturated(&info_res.stderr)
+                {
+                    return Ok(());
+                } else {
+                    eprintln!(
+                        "Error updating wallet info: {}",
+                        std::str::from_utf8(&info_res.stderr).unwrap_or("UNKNOWN ERROR")
+                    );
+                }
+            }
+            if !wallets.is_empty() {
+                insert_wallet_stats(&mut stats, &infos);
+            }
+        } else if info_res.status.code() == Some(28) || work_queue_saturated(&info_res.stderr) {
+            return Ok(());
+        } else {
+            eprintln!(
+                "Error listing wallets: {}",
+                std::str::from_utf8(&info_res.stderr).unwrap_or("UNKNOWN ERROR")
+            );
+        }
+    }
+    if let Some(zmq_addr) = config_value(config, &["zmq-tor-address"]).and_then(|v| v.as_str()) {
+        let info_res = state.rpc(config, "getzmqnotifications", &[])?;
+        if info_res.status.success() {
+            let notifications: Vec<ZmqNotification> = serde_json::from_slice(&info_res.stdout)?;
+            for notification in notifications {
+                let name = match zmq_topic_name(&notification.kind) {
+                    Some(name) => name,
+                    None => continue,
+                };
+                stats.insert(
+                    Cow::from(format!("ZeroMQ {}", name)),
+                    Stat {
+                        value_type: "string",
+                        value: zmq_endpoint(&notification.address, zmq_addr),
+                        description: Some(Cow::from(format!(
+                            "The endpoint to subscribe to for {} over Tor",
+                            name.to_lowercase()
+                        ))),
+                        copyable: true,
+                        qr: false,
+                        masked: false,
+                    },
+                );
+            }
+        } else if info_res.status.code() == Some(28) || work_queue_saturated(&info_res.stderr) {
+            return Ok(());
+        } else {
+            eprintln!(
+                "Error updating ZeroMQ notifications: {}",
+                std::str::from_utf8(&info_res.stderr).unwrap_or("UNKNOWN ERROR")
+            );
+        }
+    }
+    if let Some(listening) = p2p_listening(config, &([127, 0, 0, 1], 8333).into()) {
+        stats.insert(
+            Cow::from("P2P Listening"),
+            Stat {
+                value_type: "string",
+                value: listening.to_owned(),
+                description: Some(Cow::from(
+                    "Whether the node is accepting peer connections on its P2P port",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+    }
+    // Read once per poll; the chainstate, dbcache and mempoolrej stats all scrape it.
+    let debug_log = match read_log_tail(debug_log_path(config), 1024 * 1024) {
+        Ok(log) => Some(log),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            eprintln!("Error reading debug log: {}", e);
+            None
+        }
+    };
+    match fs::read_dir(chain_dir(config).join("chainstate")) {
+        Ok(dir) => {
+            let files = dir
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            if let Some(format) = chainstate_format(
+                &files,
+                debug_log.as_deref().unwrap_or_default(),
+                chain_tip.is_some(),
+            ) {
+                stats.insert(
+                    Cow::from("Chainstate Format"),
+                    Stat {
+                        value_type: "string",
+                        value: format.to_owned(),
+                        description: Some(Cow::from("The on-disk format of the UTXO set database")),
+                        copyable: false,
+                        qr: false,
+                        masked: false,
+                    },
+                );
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+        Err(e) => eprintln!("Error reading chainstate directory: {}", e),
+    }
+    if let Some(Ok(tor_proxy)) = onion_proxy(config).map(|proxy| proxy.parse()) {
+        stats.insert(
+            Cow::from("Tor Proxy"),
+            Stat {
+                value_type: "string",
+                value: if tcp_reachable(&tor_proxy, Duration::from_secs(2)) {
+                    "Reachable".to_owned()
+                } else {
+                    "Unreachable".to_owned()
+                },
+                description: Some(Cow::from(format!(
+                    "Whether the Tor SOCKS proxy at {} accepts connections",
+                    tor_proxy
+                ))),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+    }
+    let dbcache = config_value(config, &["advanced", "dbcache"])
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_DBCACHE_MIB);
+    stats.insert(
+        Cow::from("DB Cache"),
+        Stat {
+            value_type: "string",
+            value: format_dbcache(
+                dbcache,
+                debug_log.as_deref().and_then(coins_cache_mib),
+            ),
+            description: Some(Cow::from(
+                "How much of the configured database cache the UTXO set cache was using at the last block. A cache that stays full during the initial sync means a larger dbcache would speed it up",
+            )),
+            copyable: false,
+            qr: false,
+            masked: false,
+        },
+    );
+    let child_pid = *CHILD_PID.lock().unwrap();
+    if let Some(pid) = child_pid {
+        match fs::read_to_string(format!("/proc/{}/status", pid)) {
+            Ok(status) => {
+                if let Some(rss) = vm_rss_mib(&status) {
+                    stats.insert(
+                        Cow::from("Memory Usage"),
+                        Stat {
+                            value_type: "string",
+                            value: format!("{} MiB", rss),
+                            description: Some(Cow::from(
+                                "The resident memory used by bitcoind, including the database cache and mempool",
+                            )),
+                            copyable: false,
+                            qr: false,
+                            masked: false,
+                        },
+                    );
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => eprintln!("Error reading bitcoind memory usage: {}", e),
+        }
+    }
+    match fs::read_to_string("/proc/mounts") {
+        Ok(mounts) => {
+            if let Some((fs_type, read_write)) = datadir_filesystem(&mounts, "/root/.bitcoin") {
+                stats.insert(
+                    Cow::from("Data Filesystem"),
+                    Stat {
+                        value_type: "string",
+                        value: format!(
+                            "{} ({}){}",
+                            fs_type,
+                            if read_write { "rw" } else { "ro" },
+                            if PROBLEMATIC_FILESYSTEMS.contains(&fs_type) {
+                                " - not recommended, may cause database corruption"
+                            } else {
+                                ""
+                            }
+                        ),
+                        description: Some(Cow::from(
+                            "The filesystem backing the Bitcoin data directory and how it is mounted",
+                        )),
+                        copyable: false,
+                        qr: false,
+                        masked: false,
+                    },
+                );
+            }
+        }
+        Err(e) => eprintln!("Error reading mounts: {}", e),
+    }
+    if config_value(config, &["advanced", "stats", "mempoolrej"])
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+    {
+        if let Some(log) = &debug_log {
+            stats.insert(
+                Cow::from("Recently Rejected Txns"),
+                Stat {
+                    value_type: "string",
+                    value: format!("{}", count_rejected_txs(log)),
+                    description: Some(Cow::from(
+                        "The number of transactions recently rejected from the mempool",
+                    )),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+        }
+    }
+    if let Some((method, elapsed)) = &state.slowest_rpc {
+        stats.insert(
+            Cow::from("Slowest RPC"),
+            Stat {
+                value_type: "string",
+                value: format!("{} ({:.2}s)", method, elapsed.as_secs_f64()),
+                description: Some(Cow::from(
+                    "The slowest RPC call made while collecting these statistics, for diagnosing RPC slowness",
+                )),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+    }
+    state.write_stats(config, stats)
+}
+
+fn block_time(
+    config: &Mapping,
+    state: &mut SidecarState,
+    hash: &str,
+) -> Result<Option<u64>, Box<dyn Error>> {
+    let res = state.rpc(config, "getblockheader", &[hash.into()])?;
+    if !res.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(
+        serde_json::from_slice::<BlockHeader>(&res.stdout)?.time,
+    ))
+}
+
+/// How many headers the node still needs to catch up to its best peer, estimated from
+/// the heights peers reported on connect. `None` if no peer reported a height.
+fn headers_remaining(peers: &[PeerInfo], info: &ChainInfo) -> Option<usize> {
+    peers
+        .iter()
+        .map(|peer| peer.startingheight)
+        .filter(|height| *height >= 0)
+        .max()
+        .map(|height| (height as usize).saturating_sub(info.headers))
+}
+
+fn peer_info(
+    config: &Mapping,
+    state: &mut SidecarState,
+) -> Result<Option<Vec<PeerInfo>>, Box<dyn Error>> {
+    let res = state.rpc(config, "getpeerinfo", &[])?;
+    if !res.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_slice(&res.stdout)?))
+}
+
+fn block_relay_only_status(peers: &[PeerInfo]) -> String {
+    match peers
+        .iter()
+        .filter(|peer| peer.connection_type == "block-relay-only")
+        .count()
+    {
+        0 => "0 (reduced eclipse attack resistance)".to_owned(),
+        count => format!("{}", count),
+    }
+}
+
+/// Counts peers per network, most common first, e.g. "8 onion / 2 ipv4 / 1 i2p".
+fn peers_by_network(peers: &[PeerInfo]) -> String {
+    if peers.is_empty() {
+        return "None".to_owned();
+    }
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for peer in peers {
+        let network = match peer.network.as_str() {
+            "" => "unknown",
+            network => network,
+        };
+        match counts.iter_mut().find(|(name, _)| *name == network) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((network, 1)),
+        }
+    }
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    counts
+        .iter()
+        .map(|(network, count)| format!("{} {}", count, network))
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+/// bitcoind's estimate of the verified share of all transactions.
+fn sync_progress(info: &ChainInfo) -> String {
+    if info.blocks >= info.headers {
+        return "100%".to_owned();
+    }
+    format!("{:.2}%", 100.0 * info.verificationprogress)
+}
+
+/// Unlike `verificationprogress`, which weighs blocks by their transaction count and so
+/// sits near 100% while the last few blocks are still missing, this counts blocks.
+fn download_progress(info: &ChainInfo) -> String {
+    if info.blocks >= info.headers {
+        return "100%".to_owned();
+    }
+    format!("{:.2}%", 100.0 * info.blocks as f64 / info.headers as f64)
+}
+
+/// Header sync must run ahead of block download, which is why blocks can sit at 0
+/// for a while on a fresh node.
+fn sync_phase(info: &ChainInfo, headers_remaining: Option<usize>) -> &'static str {
+    match headers_remaining {
+        Some(remaining) if remaining > 0 => "Syncing Headers",
+        _ if info.blocks < info.headers => "Downloading Blocks",
+        _ => "Synced",
+    }
+}
+
+/// The difference in block time between the best known header and the best verified
+/// block, in seconds.
+fn header_lag(
+    config: &Mapping,
+    state: &mut SidecarState,
+    info: &ChainInfo,
+) -> Result<Option<u64>, Box<dyn Error>> {
+    let res = state.rpc(config, "getchaintips", &[])?;
+    if !res.status.success() {
+        return Ok(None);
+    }
+    let tips: Vec<ChainTip> = serde_json::from_slice(&res.stdout)?;
+    let best_header = match best_header(&tips) {
+        Some(tip) => tip,
+        None => return Ok(None),
+    };
+    Ok(time_lag(
+        block_time(config, state, &best_header.hash)?,
+        block_time(config, state, &info.bestblockhash)?,
+    ))
+}
+
+/// The highest tip bitcoind considers valid, whether or not its blocks are downloaded.
+fn best_header(tips: &[ChainTip]) -> Option<&ChainTip> {
+    tips.iter()
+        .filter(|tip| tip.status == "active" || tip.status == "headers-only")
+        .max_by_key(|tip| tip.height)
+}
+
+fn time_lag(header_time: Option<u64>, block_time: Option<u64>) -> Option<u64> {
+    Some(header_time?.saturating_sub(block_time?))
+}
+
+fn write_stats(
+    config: &Mapping,
+    stats: LinearMap<Cow<'static, str>, Stat>,
+) -> Result<(), Box<dyn Error>> {
+    let stats = Stats {
+        version: 2,
+        data: stats,
+    };
+    let mut last_write = STATS_FILES.lock().unwrap();
+    write_stats_files(
+        Path::new("/root/.bitcoin/start9"),
+        stats_format(config),
+        &stats,
+    )?;
+    *last_write = unix_now().unwrap_or_default();
+    *LATEST_STATS.lock().unwrap() = Some(stats);
+    Ok(())
+}
+
+/// `yaml`, `json` or `both`.
+fn stats_format(config: &Mapping) -> &str {
+    config_value(config, &["advanced", "stats", "format"])
+        .and_then(|v| v.as_str())
+        .unwrap_or("yaml")
+}
+
+/// Writes the stats in the configured formats, and removes a file in a format that is
+/// no longer written so that its frozen contents aren't mistaken for current stats.
+fn write_stats_files(dir: &Path, format: &str, stats: &Stats) -> Result<(), Box<dyn Error>> {
+    if format != "json" {
+        serde_yaml::to_writer(fs::File::create(dir.join(".stats.yaml.tmp"))?, stats)?;
+        fs::rename(dir.join(".stats.yaml.tmp"), dir.join("stats.yaml"))?;
+    } else {
+        remove_stale_stats(&dir.join("stats.yaml"))?;
+    }
+    if format != "yaml" {
+        serde_json::to_writer(fs::File::create(dir.join(".stats.json.tmp"))?, stats)?;
+        fs::rename(dir.join(".stats.json.tmp"), dir.join("stats.json"))?;
+    } else {
+        remove_stale_stats(&dir.join("stats.json"))?;
+    }
+    Ok(())
+}
+
+fn remove_stale_stats(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Reads back the stats file of the configured format.
+fn read_stats_file(dir: &Path, format: &str) -> Result<serde_json::Value, Box<dyn Error>> {
+    Ok(if format == "json" {
+        serde_json::from_slice(&fs::read(dir.join("stats.json"))?)?
+    } else {
+        serde_yaml::from_slice(&fs::read(dir.join("stats.yaml"))?)?
+    })
+}
+
+/// Binds the stats socket, replacing one left behind by a previous run.
+fn bind_stats_socket(path: &Path) -> std::io::Result<UnixListener> {
+    match fs::remove_file(path) {
+        Ok(()) => (),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+        Err(e) => return Err(e),
+    }
+    UnixListener::bind(path)
+}
+
+/// Hands the most recent stats snapshot, as JSON, to every client that connects.
+fn serve_stats(listener: UnixListener) {
+    for stream in listener.incoming() {
+        let res = stream
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|mut stream| {
+                // serialized up front so that a slow client doesn't hold up the sidecar
+                let snapshot = serde_json::to_vec(&*LATEST_STATS.lock().unwrap())?;
+                stream.write_all(&snapshot).map_err(Box::from)
+            });
+        if let Err(e) = res {
+            eprintln!("Error serving stats: {}", e);
+        }
+    }
+}
+
+/// Blocks per second over the sampled window.
+fn smoothed_sync_rate(samples: &std::collections::VecDeque<(Instant, usize)>) -> Option<f64> {
+    let (first_time, first_height) = samples.front()?;
+    let (last_time, last_height) = samples.back()?;
+    let elapsed = last_time.duration_since(*first_time).as_secs_f64();
+    if elapsed <= 0_f64 || last_height <= first_height {
+        return None;
+    }
+    Some((last_height - first_height) as f64 / elapsed)
+}
+
+/// Seconds until verification progress reaches 100% at the rate seen across the samples.
+/// Progress is weighted by transactions, so this tracks the slow, busy blocks near the tip
+/// better than a block count does.
+fn remaining_sync_secs(samples: &std::collections::VecDeque<(Instant, f64)>) -> Option<u64> {
+    let (first_time, first_progress) = samples.front()?;
+    let (last_time, last_progress) = samples.back()?;
+    let elapsed = last_time.duration_since(*first_time).as_secs_f64();
+    if elapsed <= 0_f64 || last_progress <= first_progress {
+        return None;
+    }
+    let rate = (last_progress - first_progress) / elapsed;
+    Some(((1_f64 - last_progress).max(0_f64) / rate).round() as u64)
+}
+
+fn estimated_completion(now: u64, remaining_blocks: usize, blocks_per_sec: f64) -> Option<u64> {
+    if blocks_per_sec <= 0_f64 {
+        return None;
+    }
+    Some(now + (remaining_blocks as f64 / blocks_per_sec).round() as u64)
+}
+
+fn should_restart_on_stall(stalled_for: Duration, threshold: Duration, restarts: u32) -> bool {
+    stalled_for >= threshold && restarts < MAX_STALL_RESTARTS
+}
+
+/// Stops bitcoind so that `inner_main` spawns it again once it has exited cleanly.
+fn request_restart() {
+    if let Some(raw_child) = *CHILD_PID.lock().unwrap() {
+        RESTART_REQUESTED.store(true, Ordering::SeqCst);
+        nix::sys::signal::kill(
+            nix::unistd::Pid::from_raw(raw_child as i32),
+            nix::sys::signal::SIGTERM,
+        )
+        .ok();
+    }
+}
+
+/// Whether bitcoind is up and not in the process of shutting down.
+fn node_running() -> bool {
+    !SHUTTING_DOWN.load(Ordering::SeqCst) && CHILD_PID.lock().unwrap().is_some()
+}
+
+/// Transaction counts are summed over all loaded wallets and masked, as they reveal
+/// how the wallets are used.
+fn insert_wallet_stats(stats: &mut LinearMap<Cow<'static, str>, Stat>, infos: &[WalletInfo]) {
+    for info in infos {
+        if let Scanning::Active { duration, progress } = info.scanning {
+            stats.insert(
+                Cow::from("Wallet Rescan"),
+                Stat {
+                    value_type: "string",
+                    value: format!(
+                        "{:.2}% ({} elapsed)",
+                        100.0 * progress,
+                        human_readable_duration(duration)
+                    ),
+                    description: Some(Cow::from("Progress of the running wallet rescan")),
+                    copyable: false,
+                    qr: false,
+                    masked: false,
+                },
+            );
+        }
+    }
+    stats.insert(
+        Cow::from("Wallet Transactions"),
+        Stat {
+            value_type: "string",
+            value: format!("{}", infos.iter().map(|info| info.txcount).sum::<u64>()),
+            description: Some(Cow::from(
+                "The number of transactions across all loaded wallets",
+            )),
+            copyable: false,
+            qr: false,
+            masked: true,
+        },
+    );
+}
+
+fn insert_softfork_stats(
+    stats: &mut LinearMap<Cow<'static, str>, Stat>,
+    softforks: LinearMap<String, SoftFork>,
+    blocks: usize,
+) {
+    for (sf_name, sf_data) in softforks {
+        let bip9 = match sf_data {
+            SoftFork::Bip9 { bip9, active: _ } => bip9,
+            _ => continue,
+        };
+        // stop showing soft fork info when it's been active for ~12 weeks
+        if bip9.status == "active" && blocks >= bip9.since + 12096 {
+            continue;
+        }
+        let sf_name_pretty = sf_name.to_title_case();
+        stats.insert(
+            Cow::from(format!("{} Status", sf_name_pretty)),
+            Stat {
+                value_type: "string",
+                value: bip9.status.to_title_case(),
+                description: Some(Cow::from(format!(
+                    "The Bip9 deployment status for {}",
+                    sf_name_pretty
+                ))),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        stats.insert(
+            Cow::from(format!("{} Start Time", sf_name_pretty)),
+            Stat {
+                value_type: "string",
+                value: bip9_time(bip9.start_time),
+                description: Some(Cow::from(format!(
+                    "The start time (UTC) of the Bip9 signaling period for {}",
+                    sf_name_pretty
+                ))),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        stats.insert(
+            Cow::from(format!("{} Timeout", sf_name_pretty)),
+            Stat {
+                value_type: "string",
+                value: bip9_time(bip9.timeout),
+                description: Some(Cow::from(format!(
+                    "The timeout time (UTC) of the Bip9 signaling period for {}",
+                    sf_name_pretty
+                ))),
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        if let (true, Some(statistics)) = (bip9.status == "started", bip9.statistics) {
+            if statistics.elapsed > 0 {
+                stats.insert(
+                    Cow::from(format!("{} Signal Percentage", sf_name_pretty)),
+                    Stat {
+                        value_type: "string",
+                        value: format!(
+                            "{:.2}%",
+                            100.0 * (statistics.count as f64) / (statistics.elapsed as f64)
+                        ),
+                        description: Some(Cow::from(format!("Percentage of the blocks in the current signaling window that are signaling for the activation of {}", sf_name_pretty))),
+                        copyable: false,
+                        qr: false,
+                        masked: false,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Formats a Bip9 start time or timeout, which use sentinel values outside the range
+/// of real timestamps.
+fn bip9_time(time: i64) -> String {
+    match u64::try_from(time) {
+        Ok(_) if time == i64::MAX => "Never".to_owned(),
+        Ok(time) => human_readable_timestamp(time),
+        Err(_) => "Always Active".to_owned(),
+    }
+}
+
+fn inner_main(reindex: bool, reindex_chainstate: bool) -> Result<(), Box<dyn Error>> {
+    while !Path::new("/root/.bitcoin/start9/config.yaml").exists() {
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    let mut config: Mapping =
+        serde_yaml::from_reader(std::fs::File::open("/root/.bitcoin/start9/config.yaml")?)?;
+    // configs saved before the network was selectable ran on the default network, and the
+    // bitcoin.conf template needs it set
+    if let Some(advanced) = config
+        .get_mut(&Value::from("advanced"))
+        .and_then(|v| v.as_mapping_mut())
+    {
+        if !advanced.contains_key(&Value::from("network")) {
+            advanced.insert(Value::from("network"), Value::from(DEFAULT_CHAIN));
+        }
+    }
+    // the bitcoin.conf template moves RPC behind the proxy whenever it runs
+    let proxied = rpc_proxied(&config);
+    if let Some(advanced) = config
+        .get_mut(&Value::from("advanced"))
+        .and_then(|v| v.as_mapping_mut())
+    {
+        let mut proxy = advanced
+            .get(&Value::from("proxy"))
+            .and_then(|v| v.as_mapping())
+            .cloned()
+            .unwrap_or_default();
+        proxy.insert(Value::from("enable"), Value::from(proxied));
+        advanced.insert(Value::from("proxy"), Value::from(proxy));
+    }
+    let config = config;
+    let sidecar_poll_interval = std::time::Duration::from_secs(
+        config_value(&config, &["advanced", "stats", "poll-interval-seconds"])
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_POLL_INTERVAL_SECS)
+            .max(MIN_POLL_INTERVAL_SECS),
+    );
+    SHUTDOWN_TIMEOUT_SECS.store(shutdown_timeout_secs(&config), Ordering::SeqCst);
+    let peer_addr = var("PEER_TOR_ADDRESS")?;
     let rpc_addr = var("RPC_TOR_ADDRESS")?;
     let mut btc_args = vec![
-        format!("-onion={}:9050", var("EMBASSY_IP")?),
-        format!("-externalip={}", peer_addr),
         "-datadir=/root/.bitcoin".to_owned(),
         "-deprecatedrpc=warnings".to_owned(),
         "-conf=/root/.bitcoin/bitcoin.conf".to_owned(),
     ];
-    if config
-        .get(&Value::String("advanced".to_owned()))
-        .and_then(|v| v.as_mapping())
-        .and_then(|v| v.get(&Value::String("peers".to_owned())))
-        .and_then(|v| v.as_mapping())
-        .and_then(|v| v.get(&Value::String("onlyonion".to_owned())))
+    let legacy_onions = legacy_onion_addresses(&config, &peer_addr);
+    if !legacy_onions.is_empty() {
+        eprintln!(
+            "Legacy v2 onion addresses are no longer supported by Tor, replace them with v3 addresses: {}",
+            legacy_onions.join(", ")
+        );
+        return Err("v2 onion addresses configured".into());
+    }
+    btc_args.extend(externalip_args(&config, &peer_addr)?);
+    if let Some(maxmempool) =
+        config_value(&config, &["advanced", "mempool", "maxmempool"]).and_then(|v| v.as_u64())
+    {
+        if maxmempool < MIN_MAXMEMPOOL_MIB {
+            return Err(format!(
+                "Invalid maxmempool: {} MiB, bitcoind requires at least {} MiB",
+                maxmempool, MIN_MAXMEMPOOL_MIB
+            )
+            .into());
+        }
+    }
+    btc_args.extend(mining_args(&config)?);
+    btc_args.extend(signet_args(&config));
+    btc_args.extend(zmq_args(&config));
+    btc_args.extend(maxtxfee_args(&config)?);
+    btc_args.extend(fallbackfee_args(&config)?);
+    btc_args.extend(acceptnonstdtxn_args(
+        chain(&config),
+        config_value(&config, &["advanced", "mempool", "acceptnonstdtxn"])
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+    )?);
+    btc_args.extend(test_peer_args(
+        chain(&config),
+        var("BITCOIND_TEST_PEERS").ok().as_deref(),
+    )?);
+    // dbcache itself is written to bitcoin.conf by the template; unset leaves bitcoind's default
+    if let Some(dbcache) = config_value(&config, &["advanced", "dbcache"]).filter(|v| !v.is_null())
+    {
+        let dbcache = match dbcache.as_u64() {
+            Some(dbcache) if dbcache > 0 => dbcache,
+            _ => return Err(format!("Invalid dbcache: {:?}", dbcache).into()),
+        };
+        if let Some(available) = fs::read_to_string("/proc/meminfo")
+            .ok()
+            .and_then(|meminfo| mem_available_mib(&meminfo))
+        {
+            if dbcache > available {
+                eprintln!(
+                    "WARNING: dbcache of {} MiB exceeds the {} MiB of available memory, bitcoind may be killed for running out of memory",
+                    dbcache, available
+                );
+            }
+        }
+    }
+    let maxconnections = match config_value(&config, &["advanced", "peers", "maxconnections"])
+        .and_then(|v| v.as_u64())
+    {
+        Some(maxconnections) if (1..=MAX_MAXCONNECTIONS).contains(&maxconnections) => {
+            Some(maxconnections)
+        }
+        Some(maxconnections) => {
+            return Err(format!(
+                "Invalid maxconnections: {}, must be between 1 and {}",
+                maxconnections, MAX_MAXCONNECTIONS
+            )
+            .into())
+        }
+        None if config_value(&config, &["advanced", "peers", "auto_maxconnections"])
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false) =>
+        {
+            let reserved = config_value(&config, &["advanced", "dbcache"])
+                .and_then(|v| v.as_u64())
+                .unwrap_or(DEFAULT_DBCACHE_MIB)
+                + config_value(&config, &["advanced", "mempool", "maxmempool"])
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(300)
+                + BASE_MEMORY_MIB;
+            match fs::read_to_string("/proc/meminfo")
+                .ok()
+                .and_then(|meminfo| mem_available_mib(&meminfo))
+            {
+                Some(available) => Some(auto_maxconnections(available, reserved)),
+                None => {
+                    eprintln!("Could not determine available memory, using default maxconnections");
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+    if let Some(maxconnections) = maxconnections {
+        btc_args.push(format!("-maxconnections={}", maxconnections));
+    }
+    if let Some(warning) = onion_only_peer_warning(
+        config_value(&config, &["advanced", "peers", "onlyonion"])
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        maxconnections,
+        config_value(&config, &["advanced", "peers", "addnode"])
+            .and_then(|v| v.as_sequence())
+            .map_or(0, |addnode| addnode.len()),
+    ) {
+        eprintln!("WARNING: {}", warning);
+    }
+    btc_args.extend(proxy_args(&config)?);
+    btc_args.extend(walletdir_args(&config, Path::new("/root/.bitcoin"))?);
+    {
+        // disable chain data backup
+        let ignore = match config_value(&config, &["advanced", "backup", "ignore"])
+            .and_then(|v| v.as_sequence())
+        {
+            Some(ignore) => ignore
+                .iter()
+                .filter_map(|v| v.as_str())
+                .map(|v| v.to_owned())
+                .collect(),
+            None => DEFAULT_BACKUP_IGNORE
+                .iter()
+                .map(|v| (*v).to_owned())
+                .collect::<Vec<_>>(),
+        };
+        fs::write(
+            "/root/.bitcoin/.backupignore",
+            backupignore(chain(&config), &ignore),
+        )?;
+    }
+    if config_value(&config, &["advanced", "logging", "debuglogfile"]).is_some() {
+        let debuglogfile = debug_log_path(&config);
+        if let Some(parent) = debuglogfile.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        btc_args.push(format!("-debuglogfile={}", debuglogfile.display()));
+    }
+    if reindex {
+        btc_args.push("-reindex".to_owned());
+        match fs::remove_file("/root/.bitcoin/requires.reindex") {
+            Ok(()) => (),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            a => a?,
+        }
+    } else if reindex_chainstate {
+        btc_args.push("-reindex-chainstate".to_owned());
+        match fs::remove_file("/root/.bitcoin/requires.reindex_chainstate") {
+            Ok(()) => (),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            a => a?,
+        }
+    }
+
+    std::io::copy(
+        &mut TemplatingReader::new(
+            std::fs::File::open("/mnt/assets/bitcoin.conf.template")?,
+            &config,
+            &"{{var}}".parse()?,
+            b'%',
+        ),
+        &mut std::fs::File::create("/root/.bitcoin/bitcoin.conf")?,
+    )?;
+    let detected = bitcoind_version()?;
+    let (xor_args, xor_note) = blocksxor_args(
+        config_value(&config, &["advanced", "blocksxor"]).and_then(|v| v.as_bool()),
+        &detected,
+        blocks_obfuscated(chain_dir(&config).join("blocks/xor.dat")),
+    );
+    if let Some(note) = xor_note {
+        eprintln!("{}", note);
+    }
+    btc_args.extend(xor_args);
+    if let Some(required) =
+        config_value(&config, &["advanced", "min_version"]).and_then(|v| v.as_str())
+    {
+        if !version_at_least(&detected, required) {
+            eprintln!(
+                "Bitcoin Core {} is older than the required minimum version {}, refusing to start",
+                detected, required
+            );
+            std::process::exit(VERSION_TOO_OLD_EXIT_CODE);
+        }
+    }
+    if config[&Value::from("advanced")][&Value::from("pruning")][&Value::from("mode")]
+        == "automatic"
+        && config_value(&config, &["advanced", "blockfilters", "blockfilterindex"])
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+        && !version_at_least(&detected, PRUNED_BLOCKFILTERINDEX_MIN_VERSION)
+    {
+        eprintln!(
+            "Bitcoin Core {} cannot build compact block filters on a pruned node, disable pruning or the block filter index",
+            detected
+        );
+        return Err("blockfilterindex is incompatible with pruning".into());
+    }
+    let nice = niceness(&config)?;
+    let par = config_value(&config, &["advanced", "performance", "par"]).and_then(|v| v.as_u64());
+    let adaptive_par = config_value(&config, &["advanced", "performance", "adaptive_par"])
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    btc_args.extend(par_args(par, adaptive_par));
+    *PIDFILE.lock().unwrap() = config_value(&config, &["advanced", "pidfile"])
+        .and_then(|v| v.as_str())
+        .map(|pidfile| Path::new("/root/.bitcoin").join(pidfile));
+    // bound before bitcoind starts so that a bad path can't leave it running unsupervised
+    if let Some(path) =
+        config_value(&config, &["advanced", "stats", "socket"]).and_then(|v| v.as_str())
+    {
+        let listener = bind_stats_socket(Path::new(path))?;
+        std::thread::spawn(move || serve_stats(listener));
+    }
+    // an invalid proxy setting must fail here, not after bitcoind is already running
+    let proxy_state = if proxied {
+        let proxy_secs = |key: &str, default: u64| -> Result<Duration, Box<dyn Error>> {
+            match config_value(&config, &["advanced", "proxy", key]).and_then(|v| v.as_u64()) {
+                Some(0) => Err(format!("Invalid proxy {}: must be greater than 0", key).into()),
+                Some(secs) => Ok(Duration::from_secs(secs)),
+                None => Ok(Duration::from_secs(default)),
+            }
+        };
+        let backpressure_secs = config_value(&config, &["advanced", "proxy", "backpressure-secs"])
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_PROXY_BACKPRESSURE_SECS);
+        // 0 has the proxy talk to bitcoind directly
+        let upstream_port = if backpressure_secs > 0 {
+            let listener = TcpListener::bind(("127.0.0.1", 0))?;
+            let port = listener.local_addr()?.port();
+            let bitcoind = ([127, 0, 0, 1], rpc_port(&config)).into();
+            let gate = Arc::new(Mutex::new(BackpressureGate {
+                cooldown: Duration::from_secs(backpressure_secs),
+                saturated_at: None,
+            }));
+            let max_connections = backpressure_max_connections(&config);
+            std::thread::spawn(move || {
+                serve_backpressure(listener, bitcoind, gate, max_connections)
+            });
+            port
+        } else {
+            rpc_port(&config)
+        };
+        Some(Arc::new(btc_rpc_proxy::State {
+            rpc_client: RpcClient::new(format!("http://127.0.0.1:{}/", upstream_port).parse()?),
+            tor: Some(TorState {
+                proxy: onion_proxy(&config).ok_or("EMBASSY_IP not set")?.parse()?,
+                only: config[&Value::from("advanced")][&Value::from("peers")]
+                    [&Value::from("onlyonion")]
+                    .as_bool()
+                    .unwrap(),
+            }),
+            peer_timeout: proxy_secs("peer-timeout-secs", DEFAULT_PROXY_PEER_TIMEOUT_SECS)?,
+            peers: tokio::sync::RwLock::new(Arc::new(Peers::new())),
+            max_peer_age: proxy_secs("max-peer-age-secs", DEFAULT_PROXY_MAX_PEER_AGE_SECS)?,
+            // 0 lifts the limit
+            max_peer_concurrency: match config_value(
+                &config,
+                &["advanced", "proxy", "max-peer-concurrency"],
+            )
+            .and_then(|v| v.as_u64())
+            {
+                Some(0) => None,
+                Some(concurrency) => Some(concurrency as usize),
+                None => Some(1),
+            },
+        }))
+    } else {
+        None
+    };
+    let (mut child, mut output_handle) = spawn_bitcoind(&btc_args, nice)?;
+    let _proxy = proxy_state.map(|state| {
+        std::thread::spawn(move || {
+            tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(btc_rpc_proxy::main(state, ([0, 0, 0, 0], 48332).into()))
+                .unwrap();
+        })
+    });
+    let auto_reindex = config_value(&config, &["advanced", "auto_reindex"])
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+    let startup_retries = config_value(&config, &["advanced", "recovery", "startup_retries"])
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_STARTUP_RETRIES) as u32;
+    let supervisor_enabled = config_value(&config, &["advanced", "supervisor", "enable"])
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let supervisor_max_restarts = config_value(&config, &["advanced", "supervisor", "max_restarts"])
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_SUPERVISOR_MAX_RESTARTS) as usize;
+    let supervisor_window = Duration::from_secs(
+        60 * config_value(&config, &["advanced", "supervisor", "window_minutes"])
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_SUPERVISOR_WINDOW_MINUTES),
+    );
+    let watched_config = config.clone();
+    std::thread::spawn(move || watch_stats(watched_config, sidecar_poll_interval));
+    let _sidecar_handle = std::thread::spawn(move || {
+        let mut state = SidecarState {
+            poll_interval: sidecar_poll_interval,
+            bitcoind_version: detected.clone(),
+            ..Default::default()
+        };
+        loop {
+            sidecar(&config, &rpc_addr, &mut state)
+                .err()
+                .map(|e| eprintln!("ERROR IN SIDECAR: {}", e));
+            std::thread::sleep(state.next_poll_delay());
+        }
+    });
+    let mut crash_restarts = std::collections::VecDeque::new();
+    let mut startup_attempts = 0;
+    let (child_res, output_tail) = loop {
+        let child_res = child.wait()?;
+        *CHILD_PID.lock().unwrap() = None;
+        remove_pidfile();
+        let output_tail = output_handle.join().unwrap_or_default();
+        if !child_res.success()
+            && !SHUTTING_DOWN.load(Ordering::SeqCst)
+            && startup_attempts < startup_retries
+            && transient_startup_failure(&output_tail)
+        {
+            let delay = STARTUP_RETRY_DELAY * 2_u32.pow(startup_attempts);
+            startup_attempts += 1;
+            eprintln!(
+                "Bitcoin Core failed to start for a transient reason, retrying in {}s ({}/{})",
+                delay.as_secs(),
+                startup_attempts,
+                startup_retries
+            );
+            std::thread::sleep(delay);
+            if SHUTTING_DOWN.load(Ordering::SeqCst) {
+                break (child_res, output_tail);
+            }
+            let (new_child, new_output_handle) = spawn_bitcoind(&btc_args, nice)?;
+            child = new_child;
+            output_handle = new_output_handle;
+            continue;
+        }
+        // corruption is left to the reindex marker and a fresh start instead
+        if supervisor_enabled
+            && !child_res.success()
+            && !SHUTTING_DOWN.load(Ordering::SeqCst)
+            && reindex_marker(&output_tail).is_none()
+        {
+            let now = Instant::now();
+            while let Some(t) = crash_restarts.front() {
+                if now.duration_since(*t) <= supervisor_window {
+                    break;
+                }
+                crash_restarts.pop_front();
+            }
+            if crash_restarts.len() < supervisor_max_restarts {
+                let delay = SUPERVISOR_RESTART_DELAY * 2_u32.pow(crash_restarts.len() as u32);
+                crash_restarts.push_back(now);
+                eprintln!(
+                    "Bitcoin Core exited unexpectedly ({}), restarting in {}s ({}/{} within {} minutes)",
+                    child_res,
+                    delay.as_secs(),
+                    crash_restarts.len(),
+                    supervisor_max_restarts,
+                    supervisor_window.as_secs() / 60
+                );
+                std::thread::sleep(delay);
+                if !SHUTTING_DOWN.load(Ordering::SeqCst) {
+                    // a reindex that was underway resumes on its own
+                    btc_args.retain(|arg| arg != "-reindex" && arg != "-reindex-chainstate");
+                    let (new_child, new_output_handle) = spawn_bitcoind(&btc_args, nice)?;
+                    child = new_child;
+                    output_handle = new_output_handle;
+                    continue;
+                }
+            } else {
+                eprintln!(
+                    "Bitcoin Core crashed {} times within {} minutes, giving up",
+                    supervisor_max_restarts,
+                    supervisor_window.as_secs() / 60
+                );
+            }
+        }
+        if RESTART_REQUESTED.swap(false, Ordering::SeqCst)
+            && child_res.success()
+            && !SHUTTING_DOWN.load(Ordering::SeqCst)
+        {
+            // a reindex requested at startup has already begun and must not start over
+            btc_args.retain(|arg| arg != "-reindex" && arg != "-reindex-chainstate");
+            // the system load may have changed since the last start
+            btc_args.retain(|arg| !arg.starts_with("-par="));
+            btc_args.extend(par_args(par, adaptive_par));
+            let (new_child, new_output_handle) = spawn_bitcoind(&btc_args, nice)?;
+            child = new_child;
+            output_handle = new_output_handle;
+            continue;
+        }
+        break (child_res, output_tail);
+    };
+    if !child_res.success() && auto_reindex {
+        if let Some(marker) = reindex_marker(&output_tail) {
+            eprintln!("Database corruption detected, creating {}", marker);
+            fs::write(Path::new("/root/.bitcoin").join(marker), "")?;
+        }
+    }
+    let code = if let Some(code) = child_res.code() {
+        code
+    } else if let Some(signal) = child_res.signal() {
+        eprintln!(
+            "PROCESS TERMINATED BY {}",
+            Signal::try_from(signal)
+                .map(|s| s.to_string())
+                .unwrap_or_else(|_| "UNKNOWN SIGNAL".to_owned())
+        );
+        128 + signal
+    } else {
+        1
+    };
+
+    std::process::exit(code)
+}
+
+/// bitcoind's `-dbcache` when none is configured.
+const DEFAULT_DBCACHE_MIB: u64 = 450;
+/// Rough memory bitcoind needs outside of dbcache, the mempool and peer connections.
+const BASE_MEMORY_MIB: u64 = 256;
+/// Worst case memory held per peer: the default 5 MB receive buffer plus 1 MB send buffer.
+const CONNECTION_MEMORY_MIB: u64 = 6;
+const MIN_AUTO_MAXCONNECTIONS: u64 = 8;
+const MAX_AUTO_MAXCONNECTIONS: u64 = 125;
+/// Upper bound accepted for a configured maxconnections, well past what any device needs.
+const MAX_MAXCONNECTIONS: u64 = 4096;
+/// Below this, an onion-only node has little room left after its outbound slots.
+const MIN_ONION_ONLY_CONNECTIONS: u64 = 16;
+/// How many trailing lines of each of bitcoind's output streams are kept to detect
+/// corruption on exit.
+const OUTPUT_TAIL_LINES: usize = 100;
+/// The ZeroMQ topics and the ports the zmq interface publishes them on. Only the full set
+/// includes `sequence`, which has no toggle of its own.
+const ZMQ_TOPICS: &[(&str, u16)] = &[
+    ("rawblock", 28332),
+    ("hashblock", 28332),
+    ("rawtx", 28333),
+    ("hashtx", 28333),
+    ("sequence", 28333),
+];
+/// The nice values Linux accepts, from highest to lowest priority.
+const NICE_RANGE: std::ops::RangeInclusive<i64> = -20..=19;
+/// bitcoind refuses a maxmempool smaller than this.
+const MIN_MAXMEMPOOL_MIB: u64 = 5;
+/// Once the mempool is this full, bitcoind starts evicting the lowest feerate transactions.
+const MEMPOOL_NEAR_FULL_PERCENT: u64 = 90;
+/// How many times bitcoind is restarted for a stalled sync before giving up, to avoid
+/// restart loops when the stall has a cause a restart can't fix.
+const MAX_STALL_RESTARTS: u32 = 3;
+const DEFAULT_STALL_MINUTES: u64 = 60;
+const DEFAULT_STARTUP_RETRIES: u64 = 3;
+const DEFAULT_SUPERVISOR_MAX_RESTARTS: u64 = 3;
+const DEFAULT_SUPERVISOR_WINDOW_MINUTES: u64 = 30;
+/// The delay before restarting bitcoind after a crash, doubled for each recent crash.
+const SUPERVISOR_RESTART_DELAY: Duration = Duration::from_secs(10);
+/// The delay before the first startup retry, doubled on each further attempt.
+const STARTUP_RETRY_DELAY: Duration = Duration::from_secs(5);
+/// How far back the sync rate is averaged over for the completion estimate.
+const SYNC_RATE_WINDOW: Duration = Duration::from_secs(600);
+/// The load average, relative to the number of CPUs, above which the system counts as
+/// under high load for adaptive `-par`.
+const HIGH_LOAD_RATIO: f64 = 0.75;
+/// The first Bitcoin Core release that can keep a block filter index while pruning.
+const PRUNED_BLOCKFILTERINDEX_MIN_VERSION: &str = "23.0";
+/// How many of the most recent blocks confirmed fee rates are sampled from.
+const RECENT_FEE_BLOCKS: usize = 6;
+/// The block template weight bounds accepted for `-blockmaxweight`; the upper one is the
+/// consensus limit.
+const MIN_BLOCKMAXWEIGHT: u64 = 4000;
+const MAX_BLOCKMAXWEIGHT: u64 = 4_000_000;
+/// Upper bound accepted for `-blockmintxfee`, in BTC/kvB.
+const MAX_BLOCKMINTXFEE: f64 = 1.0;
+/// The first Bitcoin Core release with `-blocksxor`.
+const BLOCKSXOR_MIN_VERSION: &str = "28.0";
+/// The first Bitcoin Core release with the `getchainstates` RPC.
+const GETCHAINSTATES_MIN_VERSION: &str = "26.0";
+
+/// The UTXO set cache size from the most recent `UpdateTip` line in debug.log, e.g.
+/// `cache=214.2MiB(1560042txo)`. bitcoind doesn't report it over RPC.
+fn coins_cache_mib(log: &str) -> Option<f64> {
+    let line = log.lines().rev().find(|line| line.contains("UpdateTip:"))?;
+    let cache = &line[line.find(" cache=")? + " cache=".len()..];
+    cache[..cache.find("MiB")?].parse().ok()
+}
+
+fn format_dbcache(dbcache: u64, used: Option<f64>) -> String {
+    match used {
+        Some(used) => format!(
+            "{:.1} / {} MiB ({:.0}%)",
+            used,
+            dbcache,
+            100.0 * used / dbcache as f64
+        ),
+        None => format!("{} MiB", dbcache),
+    }
+}
+
+/// Parses `MemAvailable` from the contents of `/proc/meminfo`.
+fn mem_available_mib(meminfo: &str) -> Option<u64> {
+    meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))
+        .and_then(|rest| rest.trim().strip_suffix("kB"))
+        .and_then(|kb| kb.trim().parse::<u64>().ok())
+        .map(|kb| kb / 1024)
+}
+
+fn vm_rss_mib(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|rest| rest.trim().strip_suffix("kB"))
+        .and_then(|kb| kb.trim().parse::<u64>().ok())
+        .map(|kb| kb / 1024)
+}
+
+fn serve_ratio(sent: u64, recv: u64) -> String {
+    if recv == 0 {
+        return if sent == 0 {
+            "No traffic yet".to_owned()
+        } else {
+            "Net serving".to_owned()
+        };
+    }
+    let ratio = sent as f64 / recv as f64;
+    format!(
+        "{:.2} ({})",
+        ratio,
+        if ratio >= 1_f64 {
+            "net serving"
+        } else {
+            "net consuming"
+        }
+    )
+}
+
+fn format_smart_fee(fee: &SmartFee) -> String {
+    match fee.feerate {
+        // BTC/kvB to sat/vB
+        Some(feerate) if fee.errors.is_empty() => format!("{:.1} sat/vB", feerate * 1e5),
+        _ => "Insufficient data".to_owned(),
+    }
+}
+
+/// Averages the 10th, 50th and 90th percentile fee rates across blocks.
+fn recent_feerates(percentiles: &[[f64; 5]]) -> Option<String> {
+    if percentiles.is_empty() {
+        return None;
+    }
+    let average =
+        |i: usize| percentiles.iter().map(|block| block[i]).sum::<f64>() / percentiles.len() as f64;
+    Some(format!(
+        "{:.1} / {:.1} / {:.1} sat/vB",
+        average(0),
+        average(2),
+        average(4)
+    ))
+}
+
+fn mempool_percent(usage: u64, maxmempool: u64) -> u64 {
+    match (usage * 100).checked_div(maxmempool) {
+        Some(percent) => percent.min(100),
+        None => 100,
+    }
+}
+
+fn mempool_fullness(usage: u64, maxmempool: u64) -> String {
+    let percent = mempool_percent(usage, maxmempool);
+    if percent >= MEMPOOL_NEAR_FULL_PERCENT {
+        format!("{}% full - low-fee transactions are being evicted", percent)
+    } else {
+        format!("{}% full", percent)
+    }
+}
+
+/// bitcoind raises the mempool minimum fee as it evicts transactions to stay under
+/// maxmempool, so a full mempool with a rising minimum fee means it is evicting.
+fn mempool_evicting(info: &MempoolInfo, prev_minfee: Option<f64>) -> bool {
+    mempool_percent(info.usage, info.maxmempool) >= MEMPOOL_NEAR_FULL_PERCENT
+        && matches!(prev_minfee, Some(prev) if info.mempoolminfee > prev)
+}
+
+/// Whether bitcoind exited because a previous instance still held its data directory
+/// lock or ports, which clears up on its own, as opposed to a configuration error.
+fn transient_startup_failure(output: &str) -> bool {
+    const TRANSIENT_ERRORS: &[&str] = &[
+        "Cannot obtain a lock on data directory",
+        "Unable to bind to",
+        "Unable to start HTTP server",
+    ];
+    output
+        .lines()
+        .any(|line| TRANSIENT_ERRORS.iter().any(|error| line.contains(error)))
+}
+
+/// Picks the repair marker for a corruption error printed by bitcoind. Block file
+/// corruption needs a full reindex, which also rebuilds the chainstate, so it wins
+/// when both are reported. Only messages that bitcoind prints for actual corruption
+/// are matched, since a needless full reindex takes hours.
+fn reindex_marker(output: &str) -> Option<&'static str> {
+    const BLOCK_ERRORS: &[&str] = &["Corrupted block database detected"];
+    const CHAINSTATE_ERRORS: &[&str] = &[
+        "rebuild the database using -reindex-chainstate",
+        "Please restart with -reindex-chainstate",
+    ];
+    let leveldb_corruption = |dir: &str| {
+        output
+            .lines()
+            .any(|line| line.contains("Fatal LevelDB error: Corruption") && line.contains(dir))
+    };
+    if BLOCK_ERRORS.iter().any(|e| output.contains(e)) || leveldb_corruption("/blocks/index/") {
+        Some("requires.reindex")
+    } else if CHAINSTATE_ERRORS.iter().any(|e| output.contains(e))
+        || leveldb_corruption("/chainstate/")
+    {
+        Some("requires.reindex_chainstate")
+    } else {
+        None
+    }
+}
+
+/// Builds the block template args for operators who mine against this node.
+fn mining_args(config: &Mapping) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut args = Vec::new();
+    if let Some(weight) =
+        config_value(config, &["advanced", "mining", "blockmaxweight"]).and_then(|v| v.as_u64())
+    {
+        if !(MIN_BLOCKMAXWEIGHT..=MAX_BLOCKMAXWEIGHT).contains(&weight) {
+            return Err(format!(
+                "Invalid blockmaxweight: {}, must be between {} and {}",
+                weight, MIN_BLOCKMAXWEIGHT, MAX_BLOCKMAXWEIGHT
+            )
+            .into());
+        }
+        args.push(format!("-blockmaxweight={}", weight));
+    }
+    if let Some(fee) =
+        config_value(config, &["advanced", "mining", "blockmintxfee"]).and_then(|v| v.as_f64())
+    {
+        if !(0_f64..=MAX_BLOCKMINTXFEE).contains(&fee) {
+            return Err(format!(
+                "Invalid blockmintxfee: {}, must be between 0 and {} BTC/kvB",
+                fee, MAX_BLOCKMINTXFEE
+            )
+            .into());
+        }
+        args.push(format!("-blockmintxfee={}", fee));
+    }
+    Ok(args)
+}
+
+/// `zmq-enabled` switches ZeroMQ as a whole. Topics picked under `advanced.zmq` narrow it
+/// down; with none picked every topic is published, as dependent services expect.
+fn zmq_args(config: &Mapping) -> Vec<String> {
+    if !config_value(config, &["zmq-enabled"])
         .and_then(|v| v.as_bool())
         .unwrap_or(false)
     {
-        btc_args.push(format!("-proxy={}:9050", var("EMBASSY_IP")?));
+        return Vec::new();
+    }
+    let selected = ZMQ_TOPICS
+        .iter()
+        .filter(|(topic, _)| {
+            config_value(config, &["advanced", "zmq", topic])
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false)
+        })
+        .collect::<Vec<_>>();
+    let topics = if selected.is_empty() {
+        ZMQ_TOPICS.iter().collect()
+    } else {
+        selected
+    };
+    topics
+        .into_iter()
+        .map(|(topic, port)| format!("-zmqpub{}=tcp://0.0.0.0:{}", topic, port))
+        .collect()
+}
+
+/// Custom signet parameters, which only apply when running on signet.
+fn signet_args(config: &Mapping) -> Vec<String> {
+    if chain(config) != "signet" {
+        return Vec::new();
+    }
+    let challenge = config_value(config, &["advanced", "signet", "challenge"])
+        .and_then(|v| v.as_str())
+        .map(|challenge| format!("-signetchallenge={}", challenge));
+    let seednodes = config_value(config, &["advanced", "signet", "seednode"])
+        .and_then(|v| v.as_sequence())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str())
+        .map(|seednode| format!("-signetseednode={}", seednode));
+    challenge.into_iter().chain(seednodes).collect()
+}
+
+/// Points bitcoind at the configured wallet directory under `datadir`, creating it since
+/// bitcoind refuses to start with a missing one.
+fn walletdir_args(config: &Mapping, datadir: &Path) -> std::io::Result<Vec<String>> {
+    match config_value(config, &["wallet", "walletdir"]).and_then(|v| v.as_str()) {
+        Some(walletdir) => {
+            let walletdir = datadir.join(walletdir);
+            fs::create_dir_all(&walletdir)?;
+            Ok(vec![format!("-walletdir={}", walletdir.display())])
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Formats a BTC amount as an arg, refusing amounts bitcoind can't represent: negative
+/// ones, and ones more precise than a satoshi.
+fn btc_amount_arg(name: &str, amount: f64) -> Result<String, Box<dyn Error>> {
+    let sats = amount * 1e8;
+    if !amount.is_finite() || amount < 0_f64 || (sats - sats.round()).abs() > 1e-6 {
+        return Err(format!(
+            "Invalid {}: {}, must be a positive amount with at most 8 decimal places",
+            name, amount
+        )
+        .into());
+    }
+    Ok(format!("-{}={}", name, amount))
+}
+
+/// The wallet refuses transactions paying a total fee above `-maxtxfee`.
+fn maxtxfee_args(config: &Mapping) -> Result<Vec<String>, Box<dyn Error>> {
+    match config_value(config, &["wallet", "maxtxfee"]).and_then(|v| v.as_f64()) {
+        Some(fee) if fee <= 0_f64 => Err("Invalid maxtxfee: must be greater than 0".into()),
+        Some(fee) => Ok(vec![btc_amount_arg("maxtxfee", fee)?]),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The fee rate the wallet falls back to while fee estimation lacks data, in BTC/kvB.
+fn fallbackfee_args(config: &Mapping) -> Result<Vec<String>, Box<dyn Error>> {
+    match config_value(config, &["wallet", "fallbackfee"]).and_then(|v| v.as_f64()) {
+        Some(fee) => Ok(vec![btc_amount_arg("fallbackfee", fee)?]),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// bitcoind only relays nonstandard transactions on test networks, and refuses to start
+/// with `-acceptnonstdtxn` on mainnet.
+fn acceptnonstdtxn_args(chain: &str, enabled: bool) -> Result<Vec<String>, Box<dyn Error>> {
+    match (enabled, chain) {
+        (false, _) => Ok(Vec::new()),
+        (true, "main") => {
+            Err("Accepting nonstandard transactions is only allowed on test networks".into())
+        }
+        (true, _) => Ok(vec!["-acceptnonstdtxn=1".to_owned()]),
+    }
+}
+
+/// For hermetic regtest runs: connects only to the comma-separated `peers` given in
+/// `BITCOIND_TEST_PEERS` and never looks for others. Refused on every other network.
+fn test_peer_args(chain: &str, peers: Option<&str>) -> Result<Vec<String>, Box<dyn Error>> {
+    let peers = match peers {
+        Some(peers) => peers,
+        None => return Ok(Vec::new()),
+    };
+    if chain != "regtest" {
+        return Err("BITCOIND_TEST_PEERS is only allowed on regtest".into());
+    }
+    let mut args = peers
+        .split(',')
+        .filter(|peer| !peer.is_empty())
+        .map(|peer| {
+            if !peer
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || ".:[]-".contains(c))
+            {
+                return Err(format!("Invalid test peer: {:?}", peer).into());
+            }
+            Ok(format!("-connect={}", peer))
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    args.push("-dnsseed=0".to_owned());
+    args.push("-fixedseeds=0".to_owned());
+    Ok(args)
+}
+
+/// Onion peers are scarce and slow to find, so an onion-only node with few connection
+/// slots and no known peers can struggle to stay connected.
+fn onion_only_peer_warning(
+    onlyonion: bool,
+    maxconnections: Option<u64>,
+    addnodes: usize,
+) -> Option<String> {
+    match maxconnections {
+        Some(maxconnections)
+            if onlyonion && addnodes == 0 && maxconnections < MIN_ONION_ONLY_CONNECTIONS =>
+        {
+            Some(format!(
+                "maxconnections of {} may be too low to find enough onion peers, raise it to at least {} or add onion peers to connect to",
+                maxconnections, MIN_ONION_ONLY_CONNECTIONS
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Splits whatever memory is left after the fixed reservations between peer
+/// connections, bounded so that a node always keeps enough peers to stay safe and
+/// never exceeds bitcoind's own default.
+fn auto_maxconnections(available_mib: u64, reserved_mib: u64) -> u64 {
+    (available_mib.saturating_sub(reserved_mib) / CONNECTION_MEMORY_MIB)
+        .clamp(MIN_AUTO_MAXCONNECTIONS, MAX_AUTO_MAXCONNECTIONS)
+}
+
+/// Full nodes advertise NODE_NETWORK, pruned nodes only NODE_NETWORK_LIMITED (the last 288 blocks).
+fn historical_blocks_status(services: &[String]) -> &'static str {
+    if services.iter().any(|s| s == "NETWORK") {
+        "Yes"
+    } else if services.iter().any(|s| s == "NETWORK_LIMITED") {
+        "No - pruned, serves recent blocks only"
+    } else {
+        "No"
+    }
+}
+
+fn discoverability(listening: bool, advertised_addrs: usize, services: &[String]) -> &'static str {
+    if !listening {
+        "No - not accepting connections"
+    } else if advertised_addrs == 0 {
+        "No - no address advertised"
+    } else if !services
+        .iter()
+        .any(|s| s == "NETWORK" || s == "NETWORK_LIMITED")
+    {
+        "No - not serving blocks"
+    } else {
+        "Yes"
+    }
+}
+
+/// How long bitcoind is given to exit after SIGTERM before it is killed.
+fn shutdown_timeout_secs(config: &Mapping) -> u64 {
+    config_value(config, &["advanced", "shutdown", "timeout_secs"])
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS)
+}
+
+fn listen_enabled(config: &Mapping) -> bool {
+    config_value(config, &["advanced", "peers", "listen"])
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true)
+}
+
+/// Whether bitcoind accepts connections on its P2P port at `addr`, or `None` when it
+/// isn't configured to listen.
+fn p2p_listening(config: &Mapping, addr: &SocketAddr) -> Option<&'static str> {
+    if !listen_enabled(config) {
+        return None;
+    }
+    Some(if tcp_reachable(addr, Duration::from_secs(2)) {
+        "Yes"
+    } else {
+        "No"
+    })
+}
+
+/// The onion service version of an address, with or without a port, or `None` if it
+/// isn't an onion address. v2 addresses are 16 characters and v3 addresses 56.
+fn onion_version(addr: &str) -> Option<u8> {
+    let host = match addr.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => addr,
+    };
+    match host.strip_suffix(".onion")?.len() {
+        16 => Some(2),
+        56 => Some(3),
+        _ => None,
+    }
+}
+
+/// Tor dropped v2 onion services entirely, so any left in the config can never connect.
+fn legacy_onion_addresses(config: &Mapping, peer_addr: &str) -> Vec<String> {
+    let externalip = config_value(config, &["advanced", "peers", "externalip"])
+        .and_then(|v| v.as_sequence())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str());
+    let addnode = config_value(config, &["advanced", "peers", "addnode"])
+        .and_then(|v| v.as_sequence())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_mapping())
+        .filter_map(|node| node.get(&Value::from("hostname")))
+        .filter_map(|v| v.as_str());
+    std::iter::once(peer_addr)
+        .chain(externalip)
+        .chain(addnode)
+        .filter(|addr| onion_version(addr) == Some(2))
+        .map(|addr| addr.to_owned())
+        .collect()
+}
+
+fn onion_service_status(addresses: &[LocalAddress]) -> String {
+    let versions = addresses
+        .iter()
+        .filter_map(|addr| onion_version(&addr.address))
+        .collect::<Vec<_>>();
+    if versions.is_empty() {
+        "Not advertised".to_owned()
+    } else if versions.iter().all(|version| *version == 3) {
+        "v3".to_owned()
+    } else {
+        "Legacy v2 (unsupported by Tor)".to_owned()
+    }
+}
+
+/// The StartOS provided peer address is always advertised, followed by any additional
+/// addresses the user has configured. Nothing is advertised when the node doesn't listen.
+fn externalip_args(config: &Mapping, peer_addr: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    if !listen_enabled(config) {
+        return Ok(Vec::new());
+    }
+    std::iter::once(peer_addr)
+        .chain(
+            config_value(config, &["advanced", "peers", "externalip"])
+                .and_then(|v| v.as_sequence())
+                .into_iter()
+                .flatten()
+                .filter_map(|v| v.as_str()),
+        )
+        .map(|addr| {
+            // same character set getConfig enforces, so nothing else ends up on the command line
+            if addr.is_empty()
+                || !addr
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || ".:[]-".contains(c))
+            {
+                return Err(format!("Invalid external address: {:?}", addr).into());
+            }
+            Ok(format!("-externalip={}", addr))
+        })
+        .collect()
+}
+
+/// The configured nice value, checked against the range Linux accepts so that a
+/// hand-edited config can't hand setpriority anything else.
+fn niceness(config: &Mapping) -> Result<Option<i32>, Box<dyn Error>> {
+    match config_value(config, &["advanced", "performance", "nice"]).and_then(|v| v.as_i64()) {
+        Some(nice) if NICE_RANGE.contains(&nice) => Ok(Some(nice as i32)),
+        Some(nice) => Err(format!(
+            "Invalid nice: {}, must be between {} and {}",
+            nice,
+            NICE_RANGE.start(),
+            NICE_RANGE.end()
+        )
+        .into()),
+        None => Ok(None),
+    }
+}
+
+/// Sets the scheduling priority of every thread of the process `pid`. Linux applies
+/// priorities per thread, so renicing the pid alone would miss threads bitcoind has
+/// already started.
+fn set_niceness(pid: u32, nice: i32) -> std::io::Result<()> {
+    for task in fs::read_dir(format!("/proc/{}/task", pid))? {
+        let tid = match task?.file_name().to_str().and_then(|t| t.parse().ok()) {
+            Some(tid) => tid,
+            None => continue,
+        };
+        if unsafe { nix::libc::setpriority(nix::libc::PRIO_PROCESS, tid, nice) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // bitcoind's own output stays visible by default
+    env_logger::Builder::from_env(Env::default().default_filter_or("warn,bitcoind=info")).init();
+    match std::env::args().nth(1).as_deref() {
+        Some("diagnostics") => return diagnostics(),
+        Some("rescan") => {
+            return rescan(match std::env::args().nth(2) {
+                Some(start_height) => Some(start_height.parse()?),
+                None => None,
+            })
+        }
+        _ => (),
+    }
+    let reindex = Path::new("/root/.bitcoin/requires.reindex").exists();
+    let reindex_chainstate = Path::new("/root/.bitcoin/requires.reindex_chainstate").exists();
+    ctrlc::set_handler(move || {
+        SHUTTING_DOWN.store(true, Ordering::SeqCst);
+        if let Some(raw_child) = *CHILD_PID.lock().unwrap() {
+            use nix::{
+                sys::signal::{kill, SIGKILL, SIGTERM},
+                unistd::Pid,
+            };
+            kill(Pid::from_raw(raw_child as i32), SIGTERM).unwrap();
+            let timeout = SHUTDOWN_TIMEOUT_SECS.load(Ordering::SeqCst);
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_secs(timeout));
+                if *CHILD_PID.lock().unwrap() == Some(raw_child) {
+                    eprintln!(
+                        "bitcoind did not stop within {} seconds, sending SIGKILL",
+                        timeout
+                    );
+                    // bitcoind may have exited in the meantime
+                    kill(Pid::from_raw(raw_child as i32), SIGKILL).ok();
+                }
+            });
+        } else {
+            std::process::exit(143)
+        }
+    })?;
+    inner_main(reindex, reindex_chainstate)
+}
+
+/// Reads the service config for subcommands, which may run before it has been saved.
+fn read_config() -> Result<Mapping, Box<dyn Error>> {
+    match std::fs::File::open("/root/.bitcoin/start9/config.yaml") {
+        Ok(f) => Ok(serde_yaml::from_reader(f)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Mapping::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Rescans every loaded wallet, from `start_height` or the genesis block. Progress is
+/// shown in the stats while this runs.
+fn rescan(start_height: Option<u64>) -> Result<(), Box<dyn Error>> {
+    let config = read_config()?;
+    if !acquire_rescan_lock(Path::new(RESCAN_LOCK))? {
+        eprintln!("A wallet rescan is already running");
+        std::process::exit(1);
+    }
+    let res = (|| -> Result<(), Box<dyn Error>> {
+        let wallets_res = bitcoin_cli(&config).arg("listwallets").output()?;
+        if !wallets_res.status.success() {
+            return Err(String::from_utf8_lossy(&wallets_res.stderr)
+                .into_owned()
+                .into());
+        }
+        let wallets: Vec<String> = serde_json::from_slice(&wallets_res.stdout)?;
+        for wallet in wallets {
+            println!("Rescanning wallet {:?}", wallet);
+            let status = rescan_command(&config, &wallet, start_height).status()?;
+            if !status.success() {
+                return Err(format!("Rescan of wallet {:?} failed", wallet).into());
+            }
+        }
+        Ok(())
+    })();
+    fs::remove_file(RESCAN_LOCK)?;
+    res
+}
+
+fn rescan_command(
+    config: &Mapping,
+    wallet: &str,
+    start_height: Option<u64>,
+) -> std::process::Command {
+    let mut cmd = bitcoin_cli(config);
+    // a rescan can take hours, overriding any configured client timeout
+    cmd.arg("-rpcclienttimeout=0")
+        .arg(format!("-rpcwallet={}", wallet))
+        .arg("rescanblockchain");
+    if let Some(start_height) = start_height {
+        cmd.arg(start_height.to_string());
+    }
+    cmd
+}
+
+/// Takes the lock file that keeps a second rescan from starting, clearing it first if
+/// the rescan that held it is no longer running. Returns false if a rescan is running.
+fn acquire_rescan_lock(lock: &Path) -> Result<bool, Box<dyn Error>> {
+    loop {
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(lock)
+        {
+            Ok(mut f) => {
+                write!(f, "{}", std::process::id())?;
+                return Ok(true);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                let holder = fs::read_to_string(lock).unwrap_or_default();
+                let holder = holder.trim();
+                if !holder.is_empty() && Path::new("/proc").join(holder).exists() {
+                    return Ok(false);
+                }
+                fs::remove_file(lock)?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// bitcoin.conf options whose values must never leave the node.
+const SECRET_CONF_KEYS: &[&str] = &["rpcpassword", "rpcauth", "rpccookiefile"];
+const REDACTED: &str = "[REDACTED]";
+
+/// Prints a JSON bundle of the node's state for support requests, with secrets redacted.
+fn diagnostics() -> Result<(), Box<dyn Error>> {
+    let config = read_config()?;
+    let mut bundle = serde_json::Map::new();
+    for method in &[
+        "getblockchaininfo",
+        "getnetworkinfo",
+        "getpeerinfo",
+        "getmempoolinfo",
+    ] {
+        let res = bitcoin_cli(&config).arg(method).output()?;
+        let value = if res.status.success() {
+            serde_json::from_slice(&res.stdout)?
+        } else {
+            serde_json::json!({ "error": String::from_utf8_lossy(&res.stderr).trim() })
+        };
+        bundle.insert(method.to_string(), value);
+    }
+    bundle.insert(
+        "bitcoin.conf".to_owned(),
+        match fs::read_to_string("/root/.bitcoin/bitcoin.conf") {
+            Ok(conf) => redact_conf(&conf).into(),
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
+        },
+    );
+    bundle.insert(
+        "debug.log".to_owned(),
+        match read_log_tail(debug_log_path(&config), 64 * 1024) {
+            Ok(log) => log.into(),
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
+        },
+    );
+    bundle.insert(
+        "stats".to_owned(),
+        match read_stats_file(Path::new("/root/.bitcoin/start9"), stats_format(&config)) {
+            Ok(mut stats) => {
+                redact_stats(&mut stats);
+                stats
+            }
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
+        },
+    );
+    serde_json::to_writer_pretty(std::io::stdout(), &bundle)?;
+    println!();
+    Ok(())
+}
+
+fn redact_conf(conf: &str) -> String {
+    conf.lines()
+        .map(|line| match line.split_once('=') {
+            Some((key, _)) if SECRET_CONF_KEYS.contains(&key.trim()) => {
+                format!("{}={}", key, REDACTED)
+            }
+            _ => line.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Masked stats hold credentials such as the RPC password and quick connect URLs.
+fn redact_stats(stats: &mut serde_json::Value) {
+    if let Some(data) = stats.get_mut("data").and_then(|d| d.as_object_mut()) {
+        for stat in data.values_mut() {
+            if stat
+                .get("masked")
+                .and_then(|m| m.as_bool())
+                .unwrap_or(false)
+            {
+                stat["value"] = REDACTED.into();
+            }
+        }
+    }
+}
+
+fn write_pidfile(pid: u32) {
+    if let Some(pidfile) = &*PIDFILE.lock().unwrap() {
+        if let Err(e) = fs::write(pidfile, format!("{}\n", pid)) {
+            eprintln!("Error writing pidfile {}: {}", pidfile.display(), e);
+        }
+    }
+}
+
+fn remove_pidfile() {
+    if let Some(pidfile) = &*PIDFILE.lock().unwrap() {
+        match fs::remove_file(pidfile) {
+            Ok(()) => (),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => eprintln!("Error removing pidfile {}: {}", pidfile.display(), e),
+        }
+    }
+}
+
+/// Logs a line of bitcoind's output under the `bitcoind` target, at the level bitcoind
+/// tagged it with.
+fn log_bitcoind_line(line: &str) {
+    log::log!(target: "bitcoind", bitcoind_log_level(line), "{}", line);
+}
+
+fn bitcoind_log_level(line: &str) -> log::Level {
+    if line.contains("[error]") || line.starts_with("Error:") || line.starts_with("EXCEPTION") {
+        log::Level::Error
+    } else if line.contains("[warning]") || line.starts_with("Warning:") {
+        log::Level::Warn
+    } else {
+        log::Level::Info
+    }
+}
+
+/// Starts bitcoind, forwarding its output to the log. The returned handle yields the
+/// last lines of stdout and stderr once bitcoind exits.
+fn spawn_bitcoind(
+    btc_args: &[String],
+    nice: Option<i32>,
+) -> Result<(std::process::Child, std::thread::JoinHandle<String>), Box<dyn Error>> {
+    let mut child = std::process::Command::new("bitcoind")
+        .args(btc_args)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+    // both readers hit EOF and exit once bitcoind closes its end of the pipes
+    let stdout_handle = std::thread::spawn(move || forward_output(stdout));
+    let output_handle = std::thread::spawn(move || {
+        let stderr_tail = forward_output(stderr);
+        // errors such as LevelDB corruption are logged, which goes to stdout by default
+        let stdout_tail = stdout_handle.join().unwrap_or_default();
+        format!("{}\n{}", stdout_tail, stderr_tail)
+    });
+    let raw_child = child.id();
+    *CHILD_PID.lock().unwrap() = Some(raw_child);
+    write_pidfile(raw_child);
+    if let Some(nice) = nice {
+        if let Err(e) = set_niceness(raw_child, nice) {
+            eprintln!("Error setting bitcoind niceness: {}", e);
+        }
+    }
+    Ok((child, output_handle))
+}
+
+/// Forwards a stream of bitcoind's output to the log, returning its last lines to
+/// diagnose a failed exit.
+fn forward_output(output: impl std::io::Read) -> String {
+    use std::io::BufRead;
+
+    let mut tail = std::collections::VecDeque::new();
+    let mut reader = std::io::BufReader::new(output);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        // lines that aren't valid UTF-8 are logged lossily rather than ending the stream,
+        // which would leave bitcoind writing to a closed pipe
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(e) => {
+                eprintln!("Error reading bitcoind output: {}", e);
+                break;
+            }
+        }
+        let line = String::from_utf8_lossy(&buf)
+            .trim_end_matches(&['\n', '\r'][..])
+            .to_owned();
+        log_bitcoind_line(&line);
+        if tail.len() == OUTPUT_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+    tail.into_iter().collect::<Vec<_>>().join("\n")
+}
+
+fn unix_now() -> Option<u64> {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()
+        .map(|now| now.as_secs())
+}
+
+/// Flags the stats as stale once the sidecar stops writing them, which it can't do itself
+/// when it is wedged, e.g. on an RPC call that never returns.
+fn watch_stats(config: Mapping, poll_interval: Duration) {
+    let mut flagged = 0;
+    loop {
+        std::thread::sleep(poll_interval);
+        let now = match unix_now() {
+            Some(now) => now,
+            None => continue,
+        };
+        // held through the write so that fresh stats can't be overwritten with stale ones
+        let files = STATS_FILES.lock().unwrap();
+        let last_write = *files;
+        if last_write == 0
+            || last_write == flagged
+            || !stats_stale(now.saturating_sub(last_write), poll_interval)
+        {
+            continue;
+        }
+        let stale = match &*LATEST_STATS.lock().unwrap() {
+            Some(stats) => stale_stats(stats, last_write),
+            None => continue,
+        };
+        eprintln!(
+            "Stats have not been updated since {}",
+            human_readable_timestamp(last_write)
+        );
+        match write_stats_files(
+            Path::new("/root/.bitcoin/start9"),
+            stats_format(&config),
+            &stale,
+        ) {
+            Ok(()) => flagged = last_write,
+            Err(e) => eprintln!("Error flagging stale stats: {}", e),
+        }
+        drop(files);
+    }
+}
+
+fn stale_stats(stats: &Stats, last_write: u64) -> Stats {
+    let mut stale = stats.clone();
+    if let Some(stat) = stale.data.get_mut("Stats Last Updated") {
+        stat.value = format!("{} (stale)", human_readable_timestamp(last_write));
+    }
+    stale
+}
+
+/// Allows for the longest a healthy sidecar waits between polls, which is stretched while
+/// bitcoind is under load.
+fn stats_stale(since_last_write: u64, poll_interval: Duration) -> bool {
+    since_last_write > (poll_interval * LOADED_POLL_MULTIPLIER * STALE_STATS_POLLS).as_secs()
+}
+
+fn human_readable_timestamp(unix_time: u64) -> String {
+    chrono::DateTime::<chrono::Utc>::from(
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs(unix_time),
+    )
+    .format("%m/%d/%Y @ %H:%M:%S")
+    .to_string()
+}
+
+/// The exact user agent, as peers see it, next to the parsed version.
+fn user_agent_stat(info: &NetworkInfo) -> Stat {
+    Stat {
+        value_type: "string",
+        value: info.subversion.clone(),
+        description: Some(Cow::from(
+            "The user agent this node advertises to its peers",
+        )),
+        copyable: true,
+        qr: false,
+        masked: false,
+    }
+}
+
+/// bitcoind warns about the system clock once the offset from peers exceeds 10 minutes.
+fn time_offset_unsafe(offset: i64) -> bool {
+    offset.abs() >= 10 * 60
+}
+
+/// A node is considered reachable once any inbound peer has connected, or if
+/// bitcoind has confirmed one of its advertised addresses through peers. Addresses given
+/// with `-externalip` start at `LOCAL_MANUAL_SCORE`, and only peers seeing them raise it.
+fn inbound_reachable(info: &NetworkInfo) -> bool {
+    info.connections_in > 0
+        || info
+            .localaddresses
+            .iter()
+            .any(|a| a.score > LOCAL_MANUAL_SCORE)
+}
+
+/// Reads at most the last `max_bytes` of the file at `path`.
+fn read_log_tail(path: impl AsRef<Path>, max_bytes: u64) -> std::io::Result<String> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let mut f = std::fs::File::open(path)?;
+    let len = f.metadata()?.len();
+    f.seek(SeekFrom::Start(len.saturating_sub(max_bytes)))?;
+    let mut buf = Vec::new();
+    f.read_to_end(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Counts the mempool rejections logged by bitcoind under `-debug=mempoolrej`.
+fn count_rejected_txs(log: &str) -> usize {
+    log.lines()
+        .filter(|line| line.contains(" was not accepted: "))
+        .count()
+}
+
+/// Prefers the assumevalid block reported by bitcoind, falling back to what was configured.
+fn effective_assumevalid(reported: Option<&str>, configured: Option<&str>) -> String {
+    match (reported, configured) {
+        (Some(hash), _) if assumevalid_disabled(hash) => "Disabled".to_owned(),
+        (Some(hash), _) => hash.to_owned(),
+        (None, Some(hash)) if assumevalid_disabled(hash) => "Disabled".to_owned(),
+        (None, Some(hash)) => format!("{} (configured)", hash),
+        (None, None) => "Bitcoin Core default".to_owned(),
+    }
+}
+
+/// `0` and the all-zero block hash both turn assumevalid off.
+fn assumevalid_disabled(hash: &str) -> bool {
+    hash.trim_start_matches('0').is_empty()
+}
+
+fn verification_mode(assumevalid: Option<&str>, assumeutxo: bool) -> String {
+    let mode = match assumevalid {
+        None => "Default Assume Valid",
+        Some(hash) if assumevalid_disabled(hash) => "Full Verification",
+        Some(_) => "Custom Assume Valid",
+    };
+    if assumeutxo {
+        format!("{} + AssumeUTXO Snapshot", mode)
+    } else {
+        mode.to_owned()
+    }
+}
+
+/// Finds the filesystem type of the mount containing `path` in the contents of
+/// `/proc/mounts`, along with whether it is mounted read-write.
+fn datadir_filesystem<'a>(mounts: &'a str, path: &str) -> Option<(&'a str, bool)> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = fields.nth(1)?;
+            let fs_type = fields.next()?;
+            let read_write = fields.next()?.split(',').any(|opt| opt == "rw");
+            Some((mount_point, fs_type, read_write))
+        })
+        .filter(|(mount_point, _, _)| match path.strip_prefix(mount_point) {
+            Some(rest) => rest.is_empty() || rest.starts_with('/') || mount_point.ends_with('/'),
+            None => false,
+        })
+        .max_by_key(|(mount_point, _, _)| mount_point.len())
+        .map(|(_, fs_type, read_write)| (fs_type, read_write))
+}
+
+/// Computes the (receive, send) rate in bytes per second between two samples.
+/// Returns `None` if no time has passed or the counters were reset in between.
+fn bandwidth_rate(prev: &NetTotals, cur: &NetTotals) -> Option<(f64, f64)> {
+    let elapsed = cur.timemillis.checked_sub(prev.timemillis)?;
+    if elapsed == 0 {
+        return None;
+    }
+    let recv = cur.totalbytesrecv.checked_sub(prev.totalbytesrecv)?;
+    let sent = cur.totalbytessent.checked_sub(prev.totalbytessent)?;
+    let secs = elapsed as f64 / 1000_f64;
+    Some((recv as f64 / secs, sent as f64 / secs))
+}
+
+/// bitcoind rejects requests with this message once `-rpcworkqueue` is full. When
+/// that happens the sidecar backs off until the next poll, and the RPC proxy sheds
+/// requests for a while, instead of adding to the backlog.
+fn work_queue_saturated(output: &[u8]) -> bool {
+    String::from_utf8_lossy(output).contains("Work queue depth exceeded")
+}
+
+/// Extrapolates the average size per block seen so far to estimate the height at which
+/// the chain will reach the prune target. Returns `None` before any blocks are synced or
+/// once the target has already been reached.
+fn prune_stable_height(size_on_disk: u64, blocks: usize, prune_target: u64) -> Option<usize> {
+    if blocks == 0 || size_on_disk == 0 || size_on_disk >= prune_target {
+        return None;
+    }
+    let bytes_per_block = size_on_disk as f64 / blocks as f64;
+    Some((prune_target as f64 / bytes_per_block) as usize)
+}
+
+/// The script verification threads to launch bitcoind with. Under high system load,
+/// adaptive mode only uses the idle cores so other services keep some CPU.
+fn par_args(par: Option<u64>, adaptive: bool) -> Vec<String> {
+    // unlike /proc/cpuinfo, this accounts for the container's CPU quota and affinity
+    let cpus = std::thread::available_parallelism()
+        .ok()
+        .map(|cpus| cpus.get() as u64);
+    let par = match (par.filter(|par| *par > 0), cpus) {
+        (Some(par), Some(cpus)) if par > cpus => {
+            eprintln!(
+                "par of {} exceeds the {} available CPU cores, using {}",
+                par, cpus, cpus
+            );
+            Some(cpus)
+        }
+        (par, _) => par,
+    };
+    let reduced = if adaptive {
+        match (
+            fs::read_to_string("/proc/loadavg")
+                .ok()
+                .and_then(|loadavg| load_avg_1m(&loadavg)),
+            cpus,
+        ) {
+            (Some(load), Some(cpus)) => adaptive_par(load, cpus),
+            _ => {
+                eprintln!("Could not determine system load, using configured par");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    match (par, reduced) {
+        (Some(par), Some(reduced)) => vec![format!("-par={}", std::cmp::min(par, reduced))],
+        (None, Some(reduced)) => vec![format!("-par={}", reduced)],
+        (Some(par), None) => vec![format!("-par={}", par)],
+        (None, None) => Vec::new(),
+    }
+}
+
+fn load_avg_1m(loadavg: &str) -> Option<f64> {
+    loadavg.split_whitespace().next()?.parse().ok()
+}
+
+/// Maps the 1 minute load average to a reduced `-par`, or `None` if the system isn't
+/// under high load.
+fn adaptive_par(load: f64, cpus: u64) -> Option<u64> {
+    if cpus == 0 || load < cpus as f64 * HIGH_LOAD_RATIO {
+        return None;
+    }
+    Some(((cpus as f64 - load).max(0.0) as u64).clamp(1, cpus))
+}
+
+/// Doubles the poll interval with each consecutive failed stats write, up to a cap.
+fn write_backoff_multiplier(failures: u32) -> u32 {
+    1 << failures.min(MAX_WRITE_BACKOFF_DOUBLINGS)
+}
+
+fn under_load(latency: Duration) -> bool {
+    latency >= SLOW_RPC_THRESHOLD
+}
+
+fn zmq_topic_name(kind: &str) -> Option<&'static str> {
+    match kind {
+        "pubrawblock" => Some("Raw Blocks"),
+        "pubhashblock" => Some("Block Hashes"),
+        "pubrawtx" => Some("Raw Transactions"),
+        "pubhashtx" => Some("Transaction Hashes"),
+        "pubsequence" => Some("Sequence"),
+        _ => None,
+    }
+}
+
+/// Swaps the bind host bitcoind reports (e.g. `tcp://0.0.0.0:28332`) for the address
+/// clients reach it at, keeping the port.
+fn zmq_endpoint(bound: &str, host: &str) -> String {
+    match bound.rsplit_once(':') {
+        Some((_, port)) => format!("tcp://{}:{}", host, port),
+        None => bound.to_owned(),
+    }
+}
+
+fn tcp_reachable(addr: &std::net::SocketAddr, timeout: Duration) -> bool {
+    std::net::TcpStream::connect_timeout(addr, timeout).is_ok()
+}
+
+/// The combined size of the files directly in `dir`.
+fn dir_size(dir: impl AsRef<Path>) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+/// `gettxoutsetinfo` leaves out `disk_size` when served by coinstatsindex, so the size of
+/// the chainstate directory is passed in instead.
+fn format_utxo_set(info: &TxOutSetInfo, disk_size: Option<u64>) -> String {
+    match disk_size {
+        Some(disk_size) => format!(
+            "{} outputs ({:.2} GiB)",
+            info.txouts,
+            disk_size as f64 / 1024_f64.powf(3_f64)
+        ),
+        None => format!("{} outputs", info.txouts),
+    }
+}
+
+/// The remaining upload allowance, or `None` when no maxuploadtarget is set.
+fn format_upload_target(target: &UploadTarget) -> Option<String> {
+    if target.target == 0 {
+        return None;
+    }
+    Some(format!(
+        "{}{:.2} MiB left, resets in {}",
+        if target.target_reached {
+            "Reached - "
+        } else {
+            ""
+        },
+        target.bytes_left_in_cycle as f64 / 1024_f64.powf(2_f64),
+        human_readable_duration(target.time_left_in_cycle)
+    ))
+}
+
+/// The blocks still on disk of a pruned node, from `pruneheight` (the lowest stored
+/// block) up to the tip, and roughly how many days of blocks that is.
+fn retained_blocks(blocks: usize, pruneheight: usize) -> String {
+    let retained = (blocks + 1).saturating_sub(pruneheight);
+    format!("{} (~{:.1} days)", retained, retained as f64 / 144_f64)
+}
+
+fn human_readable_duration(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// The status of an assumeutxo snapshot chainstate, if one was loaded.
+fn snapshot_status(info: &ChainStates) -> Option<&'static str> {
+    let snapshot = info
+        .chainstates
+        .iter()
+        .find(|c| c.snapshot_blockhash.is_some())?;
+    Some(if snapshot.validated {
+        "Validated"
+    } else {
+        "Validating"
+    })
+}
+
+/// bitcoind has stored the UTXO set per output since 0.15 and no longer upgrades the
+/// older per-transaction format in place, refusing to start with this message instead.
+/// The files on disk look the same either way, so a populated chainstate is only known
+/// to be per-output once bitcoind has loaded it.
+fn chainstate_format(files: &[String], log: &str, loaded: bool) -> Option<&'static str> {
+    if log.contains("Unsupported chainstate database format found") {
+        Some("Legacy Per-Transaction (requires Reindex Chainstate)")
+    } else if !files
+        .iter()
+        .any(|f| f.ends_with(".ldb") || f.ends_with(".sst"))
+    {
+        Some("Empty")
+    } else if loaded {
+        Some("Per-Output")
+    } else {
+        None
+    }
+}
+
+fn index_lag_status(index: &IndexStatus, tip: usize) -> String {
+    match tip.saturating_sub(index.best_block_height) {
+        0 => "Caught Up".to_owned(),
+        _ if index.synced => "Caught Up".to_owned(),
+        lag => format!(
+            "Behind by {} blocks ({:.2}% indexed)",
+            lag,
+            100.0 * index.best_block_height as f64 / tip as f64
+        ),
+    }
+}
+
+/// Light clients need the filter index built, and peers can only fetch filters over
+/// P2P when they are also served with `peerblockfilters`.
+fn light_client_readiness(
+    blockfilterindex: bool,
+    peerblockfilters: bool,
+    index_synced: Option<bool>,
+) -> &'static str {
+    match (blockfilterindex, peerblockfilters, index_synced) {
+        (false, _, _) => "Misconfigured - filter index is disabled",
+        (true, _, None) => "Waiting for filter index",
+        (true, _, Some(false)) => "Building filter index",
+        (true, true, Some(true)) => "Ready (serving peers)",
+        (true, false, Some(true)) => "Ready (RPC only)",
+    }
+}
+
+/// The version bitcoind reports, e.g. `v28.1.0`.
+fn bitcoind_version() -> Result<String, Box<dyn Error>> {
+    let version_res = std::process::Command::new("bitcoind")
+        .arg("-version")
+        .output()?;
+    Ok(String::from_utf8_lossy(&version_res.stdout)
+        .lines()
+        .next()
+        .and_then(|line| line.rsplit(' ').next())
+        .unwrap_or("unknown")
+        .to_owned())
+}
+
+/// Builds the `-blocksxor` arg, along with a note to log when the setting can't take
+/// effect. Releases before the option don't obfuscate at all, so the setting is left out
+/// for them without a note, as it is on by default.
+fn blocksxor_args(
+    blocksxor: Option<bool>,
+    detected: &str,
+    obfuscated: bool,
+) -> (Vec<String>, Option<&'static str>) {
+    match blocksxor {
+        Some(_) if !version_at_least(detected, BLOCKSXOR_MIN_VERSION) => (Vec::new(), None),
+        // bitcoind refuses to start if an existing key is disabled
+        Some(false) if obfuscated => (
+            Vec::new(),
+            Some("Block files are already XOR-obfuscated and stay that way until the blockchain is deleted and resynced, ignoring -blocksxor=0"),
+        ),
+        Some(blocksxor) => (vec![format!("-blocksxor={}", blocksxor as u8)], None),
+        None => (Vec::new(), None),
+    }
+}
+
+/// Whether block files were written with a non-zero XOR key.
+fn blocks_obfuscated(xor_key_path: impl AsRef<Path>) -> bool {
+    fs::read(xor_key_path)
+        .map(|key| key.iter().any(|b| *b != 0))
+        .unwrap_or(false)
+}
+
+/// Each network's name, the directory it keeps its chain data in relative to the
+/// datadir, and its default RPC port.
+const CHAINS: &[(&str, &str, u16)] = &[
+    ("main", "", 8332),
+    ("test", "testnet3/", 18332),
+    ("testnet4", "testnet4/", 48332),
+    ("signet", "signet/", 38332),
+    ("regtest", "regtest/", 18443),
+];
+
+/// The network bitcoind runs on, matching `chain=` in bitcoin.conf.
+fn chain(config: &Mapping) -> &str {
+    config_value(config, &["advanced", "network"])
+        .and_then(|v| v.as_str())
+        .unwrap_or(DEFAULT_CHAIN)
+}
+
+/// The directory the configured network keeps its chain data in.
+fn chain_dir(config: &Mapping) -> std::path::PathBuf {
+    let chain = chain(config);
+    let dir = CHAINS
+        .iter()
+        .find(|(name, _, _)| *name == chain)
+        .map_or("", |(_, dir, _)| *dir);
+    Path::new("/root/.bitcoin").join(dir)
+}
+
+/// Builds the `.backupignore` contents: the given directories of the active chain, and
+/// the chain data of the other networks. Everything else of an inactive network, such as
+/// its wallets, is still backed up.
+fn backupignore(chain: &str, ignore: &[String]) -> String {
+    let mut out = String::new();
+    for (name, dir, _) in CHAINS {
+        let entries = if *name == chain {
+            ignore
+                .iter()
+                .map(|entry| entry.as_str())
+                .collect::<Vec<_>>()
+        } else {
+            DEFAULT_BACKUP_IGNORE.to_vec()
+        };
+        for entry in entries {
+            out.push_str(&format!("{}{}/\n", dir, entry.trim_end_matches('/')));
+        }
+        // the mempool is stale by the time a backup is restored
+        out.push_str(&format!("{}mempool.dat\n", dir));
+    }
+    out
+}
+
+/// Parses a version such as `v28.1.0` or `28.1` into its numeric components, ignoring
+/// any suffix like `rc1` or `-dirty`.
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = version
+        .trim()
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| {
+            part.chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect::<String>()
+                .parse::<u32>()
+        });
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().and_then(|p| p.ok()).unwrap_or(0);
+    let patch = parts.next().and_then(|p| p.ok()).unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+/// An unparseable detected version never satisfies the requirement.
+fn version_at_least(detected: &str, required: &str) -> bool {
+    match (parse_version(detected), parse_version(required)) {
+        (Some(detected), Some(required)) => detected >= required,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(yaml: &str) -> Mapping {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn args(cmd: &std::process::Command) -> Vec<String> {
+        cmd.get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn bitcoin_cli_passes_rpcclienttimeout() {
+        let cmd = bitcoin_cli(&config("advanced: {stats: {cli_client_timeout: 30}}"));
+        assert!(args(&cmd).contains(&"-rpcclienttimeout=30".to_owned()));
+        let cmd = bitcoin_cli(&config("advanced: {stats: {}}"));
+        assert!(!args(&cmd)
+            .iter()
+            .any(|arg| arg.starts_with("-rpcclienttimeout")));
+    }
+
+    fn network_info(json: serde_json::Value) -> NetworkInfo {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn inbound_reachable_from_peers_or_confirmed_address() {
+        let unreachable = network_info(serde_json::json!({
+            "connections": 8,
+            "connections_in": 0,
+            "connections_out": 8,
+            "localaddresses": [{"address": "abc.onion", "port": 8333, "score": 1}],
+        }));
+        assert!(!inbound_reachable(&unreachable));
+        // only the address the manager seeds with -externalip, unconfirmed by any peer
+        let seeded = network_info(serde_json::json!({
+            "connections": 8,
+            "connections_in": 0,
+            "connections_out": 8,
+            "localaddresses": [{"address": "abc.onion", "port": 8333, "score": 4}],
+        }));
+        assert!(!inbound_reachable(&seeded));
+        let inbound = network_info(serde_json::json!({
+            "connections": 9,
+            "connections_in": 1,
+            "connections_out": 8,
+        }));
+        assert!(inbound_reachable(&inbound));
+        let confirmed = network_info(serde_json::json!({
+            "connections": 8,
+            "connections_in": 0,
+            "connections_out": 8,
+            "localaddresses": [{"address": "abc.onion", "port": 8333, "score": 5}],
+        }));
+        assert!(inbound_reachable(&confirmed));
+    }
+
+    #[test]
+    fn mining_args_from_config() {
+        assert!(mining_args(&config("advanced: {mining: {}}"))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            mining_args(&config(
+                "advanced: {mining: {blockmaxweight: 3996000, blockmintxfee: 0.00001}}"
+            ))
+            .unwrap(),
+            vec!["-blockmaxweight=3996000", "-blockmintxfee=0.00001"]
+        );
+        assert!(mining_args(&config("advanced: {mining: {blockmaxweight: 4000001}}")).is_err());
+        assert!(mining_args(&config("advanced: {mining: {blockmaxweight: 3999}}")).is_err());
+        assert!(mining_args(&config("advanced: {mining: {blockmintxfee: 1.5}}")).is_err());
+    }
+
+    #[test]
+    fn count_rejected_txs_in_log() {
+        assert_eq!(
+            count_rejected_txs(
+                "2024-05-01T10:00:00Z UpdateTip: new best=00000000 height=840000\n\
+                 2024-05-01T10:00:01Z [mempool] AcceptToMemoryPool: peer=3: accepted 1a2b\n"
+            ),
+            0
+        );
+        assert_eq!(
+            count_rejected_txs(
+                "2024-05-01T10:00:00Z [mempoolrej] 1a2b from peer=3 was not accepted: min relay fee not met, 100 < 200\n\
+                 2024-05-01T10:00:01Z UpdateTip: new best=00000000 height=840000\n\
+                 2024-05-01T10:00:02Z [mempoolrej] 3c4d from peer=5 was not accepted: txn-mempool-conflict\n"
+            ),
+            2
+        );
+    }
+
+    #[test]
+    fn maxtxfee_args_from_config() {
+        assert!(maxtxfee_args(&config("wallet: {}")).unwrap().is_empty());
+        assert_eq!(
+            maxtxfee_args(&config("wallet: {maxtxfee: 0.05}")).unwrap(),
+            vec!["-maxtxfee=0.05"]
+        );
+        assert_eq!(
+            maxtxfee_args(&config("wallet: {maxtxfee: 0.00000001}")).unwrap(),
+            vec!["-maxtxfee=0.00000001"]
+        );
+        assert!(maxtxfee_args(&config("wallet: {maxtxfee: 0}")).is_err());
+        assert!(maxtxfee_args(&config("wallet: {maxtxfee: -0.1}")).is_err());
+        // finer than a satoshi
+        assert!(maxtxfee_args(&config("wallet: {maxtxfee: 0.123456789}")).is_err());
+    }
+
+    #[test]
+    fn verification_mode_across_configs() {
+        assert_eq!(verification_mode(None, false), "Default Assume Valid");
+        assert_eq!(verification_mode(Some("0"), false), "Full Verification");
+        assert_eq!(
+            verification_mode(
+                Some("0000000000000000000000000000000000000000000000000000000000000000"),
+                false
+            ),
+            "Full Verification"
+        );
+        assert_eq!(
+            verification_mode(
+                Some("00000000000000000001a0a448d6cf2546b06801389cc030b2b18c6491266815"),
+                false
+            ),
+            "Custom Assume Valid"
+        );
+        assert_eq!(
+            verification_mode(None, true),
+            "Default Assume Valid + AssumeUTXO Snapshot"
+        );
+        assert_eq!(
+            verification_mode(Some("0"), true),
+            "Full Verification + AssumeUTXO Snapshot"
+        );
+    }
+
+    #[test]
+    fn bitcoin_cli_stays_on_loopback_with_onlynet() {
+        let cmd = bitcoin_cli(&config(
+            "advanced: {peers: {onlyonion: true, proxy: '10.0.3.1:9050'}}",
+        ));
+        let args = args(&cmd);
+        assert!(args.contains(&"-rpcconnect=127.0.0.1".to_owned()));
+        assert!(!args
+            .iter()
+            .any(|arg| arg.starts_with("-onlynet") || arg.starts_with("-proxy")));
+    }
+
+    #[test]
+    fn datadir_filesystem_picks_longest_mount() {
+        let mounts = "overlay / overlay rw,relatime 0 0\n\
+                      proc /proc proc rw,nosuid 0 0\n\
+                      /dev/sda1 /root ext4 rw,relatime 0 0\n\
+                      server:/export /root/.bitcoin nfs4 ro,relatime 0 0\n\
+                      /dev/sdb1 /root/.bitcoin-old btrfs rw 0 0\n";
+        assert_eq!(
+            datadir_filesystem(mounts, "/root/.bitcoin"),
+            Some(("nfs4", false))
+        );
+        assert_eq!(
+            datadir_filesystem(mounts, "/root/.bitcoin/blocks"),
+            Some(("nfs4", false))
+        );
+        // a mount point sharing a name prefix isn't a parent
+        assert_eq!(
+            datadir_filesystem(
+                "overlay / overlay rw 0 0\n/dev/sdb1 /root/.bitcoin-old btrfs rw 0 0\n",
+                "/root/.bitcoin"
+            ),
+            Some(("overlay", true))
+        );
+        assert_eq!(datadir_filesystem("", "/root/.bitcoin"), None);
+    }
+
+    #[test]
+    fn signet_args_gated_on_chain() {
+        let signet = "advanced: {signet: {challenge: '512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d1e086be43051ae', seednode: ['178.128.221.177', 'seed.example.com:38333']}";
+        assert_eq!(
+            signet_args(&config(&format!("{}, network: signet}}", signet))),
+            vec![
+                "-signetchallenge=512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d1e086be43051ae",
+                "-signetseednode=178.128.221.177",
+                "-signetseednode=seed.example.com:38333",
+            ]
+        );
+        assert!(signet_args(&config(&format!("{}, network: testnet4}}", signet))).is_empty());
+        assert!(signet_args(&config(&format!("{}}}", signet))).is_empty());
+        assert!(signet_args(&config(
+            "advanced: {network: signet, signet: {seednode: []}}"
+        ))
+        .is_empty());
+    }
+
+    fn net_totals(recv: u64, sent: u64, timemillis: u64) -> NetTotals {
+        serde_json::from_value(serde_json::json!({
+            "totalbytesrecv": recv,
+            "totalbytessent": sent,
+            "timemillis": timemillis,
+            "uploadtarget": {
+                "timeframe": 86400,
+                "target": 0,
+                "target_reached": false,
+                "bytes_left_in_cycle": 0,
+                "time_left_in_cycle": 0,
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn bandwidth_rate_between_samples() {
+        assert_eq!(
+            bandwidth_rate(
+                &net_totals(1_000, 500, 10_000),
+                &net_totals(11_000, 2_500, 15_000)
+            ),
+            Some((2_000_f64, 400_f64))
+        );
+        // no time passed
+        assert_eq!(
+            bandwidth_rate(
+                &net_totals(1_000, 500, 10_000),
+                &net_totals(2_000, 500, 10_000)
+            ),
+            None
+        );
+        // counters reset by a restart
+        assert_eq!(
+            bandwidth_rate(&net_totals(1_000, 500, 10_000), &net_totals(10, 5, 15_000)),
+            None
+        );
+    }
+
+    #[test]
+    fn backpressure_sheds_after_work_queue_full() {
+        let start = Instant::now();
+        let mut gate = BackpressureGate {
+            cooldown: Duration::from_secs(2),
+            saturated_at: None,
+        };
+        assert_eq!(gate.decide(start), Backpressure::Forward);
+        gate.record(start, 200, b"{\"result\":1,\"error\":null,\"id\":1}");
+        gate.record(start, 503, b"Service Unavailable");
+        assert_eq!(gate.decide(start), Backpressure::Forward);
+        gate.record(start, 503, b"Work queue depth exceeded");
+        assert_eq!(gate.decide(start), Backpressure::Shed);
+        assert_eq!(
+            gate.decide(start + Duration::from_secs(1)),
+            Backpressure::Shed
+        );
+        assert_eq!(
+            gate.decide(start + Duration::from_secs(2)),
+            Backpressure::Forward
+        );
+    }
+
+    #[test]
+    fn backpressure_forwards_until_saturated() {
+        use std::io::Read;
+
+        let upstream = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let upstream_addr = upstream.local_addr().unwrap();
+        let upstream_requests = Arc::new(AtomicU64::new(0));
+        let upstream_connections = Arc::new(AtomicU64::new(0));
+        let (requests, connections) = (upstream_requests.clone(), upstream_connections.clone());
+        std::thread::spawn(move || {
+            for stream in upstream.incoming() {
+                let stream = stream.unwrap();
+                connections.fetch_add(1, Ordering::SeqCst);
+                let mut reader = std::io::BufReader::new(&stream);
+                while let Some(head) = read_http_head(&mut reader).unwrap() {
+                    reader
+                        .read_exact(&mut vec![0; head.content_length])
+                        .unwrap();
+                    let (status, body) = if requests.fetch_add(1, Ordering::SeqCst) == 0 {
+                        ("200 OK", "{\"result\":1,\"error\":null,\"id\":1}")
+                    } else {
+                        ("503 Service Unavailable", "Work queue depth exceeded")
+                    };
+                    write!(
+                        &stream,
+                        "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    )
+                    .unwrap();
+                }
+            }
+        });
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let gate = Arc::new(Mutex::new(BackpressureGate {
+            cooldown: Duration::from_secs(60),
+            saturated_at: None,
+        }));
+        std::thread::spawn(move || serve_backpressure(listener, upstream_addr, gate, 1));
+        let send = |client: &TcpStream| {
+            let body = b"{\"method\":\"getblockcount\"}";
+            write!(
+                &*client,
+                "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                body.len()
+            )
+            .unwrap();
+            (&*client).write_all(body).unwrap();
+            let mut reader = std::io::BufReader::new(client);
+            let head = read_http_head(&mut reader).unwrap().unwrap();
+            reader
+                .read_exact(&mut vec![0; head.content_length])
+                .unwrap();
+            (http_status(&head).unwrap(), head.keep_alive)
+        };
+        let client = TcpStream::connect(addr).unwrap();
+        let statuses = (0..3).map(|_| send(&client).0).collect::<Vec<_>>();
+        assert_eq!(statuses, vec![200, 503, 503]);
+        // the last request was shed without reaching bitcoind
+        assert_eq!(upstream_requests.load(Ordering::SeqCst), 2);
+        // and both that did reached it over the same connection
+        assert_eq!(upstream_connections.load(Ordering::SeqCst), 1);
+        // a second connection is over the limit of one
+        assert_eq!(send(&TcpStream::connect(addr).unwrap()), (503, false));
+        assert_eq!(upstream_requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn connection_limit_frees_slots_on_drop() {
+        let limit = Arc::new(ConnectionLimit {
+            active: AtomicUsize::new(0),
+            max: 2,
+        });
+        let first = limit.try_acquire().unwrap();
+        let _second = limit.try_acquire().unwrap();
+        assert!(limit.try_acquire().is_none());
+        drop(first);
+        assert!(limit.try_acquire().is_some());
+        assert_eq!(
+            backpressure_max_connections(&config("rpc: {advanced: {threads: 4, workqueue: 16}}")),
+            20
+        );
+        assert_eq!(backpressure_max_connections(&config("rpc: {}")), 144);
+    }
+
+    #[test]
+    fn prune_stable_height_extrapolates_block_size() {
+        // 1 MB per block so far, with a 550 MB target
+        assert_eq!(
+            prune_stable_height(100_000_000, 100, 550_000_000),
+            Some(550)
+        );
+        assert_eq!(prune_stable_height(0, 0, 550_000_000), None);
+        // already at the target
+        assert_eq!(prune_stable_height(550_000_000, 550, 550_000_000), None);
+    }
+
+    #[test]
+    fn fallbackfee_args_from_config() {
+        assert!(fallbackfee_args(&config("wallet: {}")).unwrap().is_empty());
+        assert_eq!(
+            fallbackfee_args(&config("wallet: {fallbackfee: 0.0002}")).unwrap(),
+            vec!["-fallbackfee=0.0002"]
+        );
+        // 0 disables the fallback
+        assert_eq!(
+            fallbackfee_args(&config("wallet: {fallbackfee: 0}")).unwrap(),
+            vec!["-fallbackfee=0"]
+        );
+        assert!(fallbackfee_args(&config("wallet: {fallbackfee: -0.0001}")).is_err());
+    }
+
+    #[test]
+    fn tcp_reachable_probe() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert!(tcp_reachable(&addr, Duration::from_secs(2)));
+        drop(listener);
+        assert!(!tcp_reachable(&addr, Duration::from_secs(2)));
+    }
+
+    /// A fresh directory for a test to write to.
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "bitcoind-manager-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn walletdir_args_create_the_directory() {
+        let datadir = test_dir("walletdir");
+        assert!(walletdir_args(&config("wallet: {}"), &datadir)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            walletdir_args(&config("wallet: {walletdir: wallets/main}"), &datadir).unwrap(),
+            vec![format!(
+                "-walletdir={}",
+                datadir.join("wallets/main").display()
+            )]
+        );
+        assert!(datadir.join("wallets/main").is_dir());
+        // an existing directory is left as is
+        assert!(walletdir_args(&config("wallet: {walletdir: wallets/main}"), &datadir).is_ok());
+        fs::remove_dir_all(&datadir).unwrap();
+    }
+
+    #[test]
+    fn parse_deploymentinfo() {
+        let info: DeploymentInfo = serde_json::from_str(
+            r#"{
+                "hash": "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5",
+                "height": 840000,
+                "deployments": {
+                    "bip34": {"type": "buried", "active": true, "height": 227931},
+                    "taproot": {
+                        "type": "bip9",
+                        "height": 709632,
+                        "active": true,
+                        "bip9": {
+                            "start_time": 1619222400,
+                            "timeout": 1628640000,
+                            "min_activation_height": 709632,
+                            "status": "active",
+                            "since": 709632,
+                            "status_next": "active"
+                        }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            info.deployments.get("bip34"),
+            Some(SoftFork::Buried {
+                active: true,
+                height: 227931
+            })
+        ));
+        match info.deployments.get("taproot") {
+            Some(SoftFork::Bip9 { active: true, bip9 }) => {
+                assert_eq!(bip9.status, "active");
+                assert_eq!(bip9.since, 709632);
+            }
+            other => panic!("unexpected taproot deployment: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_legacy_softforks() {
+        let info: ChainInfo = serde_json::from_str(
+            r#"{
+                "chain": "main",
+                "blocks": 700000,
+                "headers": 700000,
+                "bestblockhash": "0000000000000000000590fc0f3eba193a278534220b2b37e9849e1a770ca959",
+                "verificationprogress": 0.9999,
+                "size_on_disk": 400000000000,
+                "softforks": {
+                    "segwit": {"type": "buried", "active": true, "height": 481824},
+                    "taproot": {
+                        "type": "bip9",
+                        "bip9": {
+                            "status": "locked_in",
+                            "start_time": 1619222400,
+                            "timeout": 1628640000,
+                            "since": 687456,
+                            "min_activation_height": 709632
+                        },
+                        "active": false
+                    },
+                    "future": {"type": "something-new", "active": false}
+                },
+                "warnings": ""
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(info.softforks.len(), 3);
+        assert!(matches!(
+            info.softforks.get("taproot"),
+            Some(SoftFork::Bip9 { active: false, .. })
+        ));
+        assert!(matches!(
+            info.softforks.get("future"),
+            Some(SoftFork::Unknown)
+        ));
+        // newer versions leave softforks out of getblockchaininfo
+        let info: ChainInfo = serde_json::from_str(
+            r#"{"blocks": 1, "headers": 1, "bestblockhash": "00", "verificationprogress": 1.0, "size_on_disk": 1}"#,
+        )
+        .unwrap();
+        assert!(info.softforks.is_empty());
+    }
+
+    #[test]
+    fn format_utxo_set_with_and_without_disk_size() {
+        let info: TxOutSetInfo = serde_json::from_str(
+            r#"{"height": 840000, "txouts": 176946140, "total_unspendable_amount": 220.9}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            format_utxo_set(&info, Some(11811160064)),
+            "176946140 outputs (11.00 GiB)"
+        );
+        assert_eq!(format_utxo_set(&info, None), "176946140 outputs");
+
+        let dir = test_dir("chainstate-size");
+        fs::write(dir.join("000001.ldb"), vec![0; 1000]).unwrap();
+        fs::write(dir.join("MANIFEST-000002"), vec![0; 24]).unwrap();
+        fs::create_dir(dir.join("nested")).unwrap();
+        assert_eq!(dir_size(&dir).unwrap(), 1024);
+        fs::remove_dir_all(&dir).ok();
+        assert!(dir_size(&dir).is_err());
+    }
+
+    #[test]
+    fn node_running_tracks_child() {
+        *CHILD_PID.lock().unwrap() = None;
+        assert!(!node_running());
+        *CHILD_PID.lock().unwrap() = Some(std::process::id());
+        assert!(node_running());
+        *CHILD_PID.lock().unwrap() = None;
+        assert!(!node_running());
+    }
+
+    #[test]
+    fn parse_upload_target() {
+        let target: UploadTarget = serde_json::from_str(
+            r#"{
+                "timeframe": 86400,
+                "target": 5242880000,
+                "target_reached": false,
+                "serve_historical_blocks": true,
+                "bytes_left_in_cycle": 3145728000,
+                "time_left_in_cycle": 5400
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            format_upload_target(&target).unwrap(),
+            "3000.00 MiB left, resets in 1h 30m"
+        );
+        let target: UploadTarget = serde_json::from_str(
+            r#"{
+                "timeframe": 86400,
+                "target": 5242880000,
+                "target_reached": true,
+                "serve_historical_blocks": false,
+                "bytes_left_in_cycle": 0,
+                "time_left_in_cycle": 120
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            format_upload_target(&target).unwrap(),
+            "Reached - 0.00 MiB left, resets in 2m"
+        );
+        // no maxuploadtarget configured
+        assert_eq!(
+            format_upload_target(&net_totals(0, 0, 0).uploadtarget),
+            None
+        );
+    }
+
+    #[test]
+    fn externalip_args_advertise_every_address() {
+        let onion = "abcdefghijklmnopqrstuvwxyzabcdefghijklmnopqrstuvwxyz2345.onion";
+        assert_eq!(
+            externalip_args(&config("advanced: {peers: {}}"), onion).unwrap(),
+            vec![format!("-externalip={}", onion)]
+        );
+        assert_eq!(
+            externalip_args(
+                &config(
+                    "advanced: {peers: {externalip: ['203.0.113.7', '[2001:db8::1]:8333', 'node.example.com']}}"
+                ),
+                onion
+            )
+            .unwrap(),
+            vec![
+                format!("-externalip={}", onion),
+                "-externalip=203.0.113.7".to_owned(),
+                "-externalip=[2001:db8::1]:8333".to_owned(),
+                "-externalip=node.example.com".to_owned(),
+            ]
+        );
+        assert!(externalip_args(
+            &config("advanced: {peers: {externalip: ['1.2.3.4 -connect=evil']}}"),
+            onion
+        )
+        .is_err());
+        assert!(externalip_args(&config("advanced: {peers: {externalip: ['']}}"), onion).is_err());
+    }
+
+    #[test]
+    fn snapshot_status_from_chainstates() {
+        let states = |json: &str| -> ChainStates { serde_json::from_str(json).unwrap() };
+        assert_eq!(
+            snapshot_status(&states(
+                r#"{"headers": 840000, "chainstates": [{"blocks": 840000, "validated": true}]}"#
+            )),
+            None
+        );
+        assert_eq!(
+            snapshot_status(&states(
+                r#"{"headers": 840000, "chainstates": [
+                    {"blocks": 120000, "validated": true},
+                    {"blocks": 840000, "snapshot_blockhash": "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5", "validated": false}
+                ]}"#
+            )),
+            Some("Validating")
+        );
+        assert_eq!(
+            snapshot_status(&states(
+                r#"{"headers": 840000, "chainstates": [
+                    {"blocks": 840000, "snapshot_blockhash": "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5", "validated": true}
+                ]}"#
+            )),
+            Some("Validated")
+        );
+    }
+
+    #[test]
+    fn adaptive_polling_slows_down_under_load() {
+        assert!(!under_load(Duration::from_millis(100)));
+        assert!(under_load(SLOW_RPC_THRESHOLD));
+        assert!(under_load(Duration::from_secs(30)));
+        let mut state = SidecarState {
+            poll_interval: Duration::from_secs(5),
+            ..Default::default()
+        };
+        assert_eq!(state.next_poll_delay(), Duration::from_secs(5));
+        state.under_load = true;
+        assert_eq!(
+            state.next_poll_delay(),
+            Duration::from_secs(5) * LOADED_POLL_MULTIPLIER
+        );
+    }
+
+    #[test]
+    fn time_offset_threshold() {
+        assert!(!time_offset_unsafe(0));
+        assert!(!time_offset_unsafe(599));
+        assert!(!time_offset_unsafe(-599));
+        assert!(time_offset_unsafe(600));
+        assert!(time_offset_unsafe(-600));
+        assert!(time_offset_unsafe(3600));
+    }
+
+    #[test]
+    fn set_niceness_applies_to_child() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let res = set_niceness(child.id(), 7);
+        let stat = fs::read_to_string(format!("/proc/{}/stat", child.id())).unwrap();
+        child.kill().ok();
+        child.wait().ok();
+        res.unwrap();
+        // the nice value is the 19th field, counted after the parenthesised command name
+        let fields: Vec<&str> = stat[stat.rfind(')').unwrap() + 2..].split(' ').collect();
+        assert_eq!(fields[16], "7");
+        assert!(set_niceness(u32::MAX, 7).is_err());
+    }
+
+    #[test]
+    fn p2p_listening_check() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert_eq!(
+            p2p_listening(&config("advanced: {peers: {}}"), &addr),
+            Some("Yes")
+        );
+        assert_eq!(
+            p2p_listening(&config("advanced: {peers: {listen: true}}"), &addr),
+            Some("Yes")
+        );
+        assert_eq!(
+            p2p_listening(&config("advanced: {peers: {listen: false}}"), &addr),
+            None
+        );
+        drop(listener);
+        assert_eq!(
+            p2p_listening(&config("advanced: {peers: {listen: true}}"), &addr),
+            Some("No")
+        );
+    }
+
+    #[test]
+    fn auto_maxconnections_from_memory() {
+        let meminfo = "MemTotal:        8049100 kB\nMemFree:          512000 kB\nMemAvailable:    2097152 kB\n";
+        assert_eq!(mem_available_mib(meminfo), Some(2048));
+        assert_eq!(mem_available_mib("MemTotal: 8049100 kB\n"), None);
+        // 2048 MiB available, 1748 MiB reserved leaves 300 MiB for connections
+        assert_eq!(auto_maxconnections(2048, 1748), 300 / CONNECTION_MEMORY_MIB);
+        assert_eq!(auto_maxconnections(512, 1024), MIN_AUTO_MAXCONNECTIONS);
+        assert_eq!(auto_maxconnections(65536, 1024), MAX_AUTO_MAXCONNECTIONS);
+    }
+
+    #[test]
+    fn chainstate_format_needs_evidence() {
+        let files = vec![
+            "000123.ldb".to_owned(),
+            "CURRENT".to_owned(),
+            "MANIFEST-000120".to_owned(),
+        ];
+        let legacy = "2026-10-17T12:00:00Z Unsupported chainstate database format found. Please restart with -reindex-chainstate. This will rebuild the chainstate database.\n";
+        assert_eq!(
+            chainstate_format(&files, legacy, false),
+            Some("Legacy Per-Transaction (requires Reindex Chainstate)")
+        );
+        assert_eq!(chainstate_format(&files, "", false), None);
+        assert_eq!(chainstate_format(&files, "", true), Some("Per-Output"));
+        assert_eq!(
+            chainstate_format(&["LOCK".to_owned(), "CURRENT".to_owned()], "", false),
+            Some("Empty")
+        );
+        assert_eq!(chainstate_format(&[], "", true), Some("Empty"));
+    }
+
+    #[test]
+    fn shutdown_timeout_from_config() {
+        assert_eq!(
+            shutdown_timeout_secs(&config("advanced: {}")),
+            DEFAULT_SHUTDOWN_TIMEOUT_SECS
+        );
+        assert_eq!(
+            shutdown_timeout_secs(&config("advanced: {shutdown: {timeout_secs: 120}}")),
+            120
+        );
+    }
+
+    #[test]
+    fn blocks_this_session_resets_on_respawn() {
+        let mut state = SidecarState::default();
+        assert_eq!(state.blocks_this_session(Some(100), 800_000), 0);
+        assert_eq!(state.blocks_this_session(Some(100), 800_050), 50);
+        // bitcoind was restarted, e.g. after a stall or crash
+        assert_eq!(state.blocks_this_session(Some(200), 800_060), 0);
+        assert_eq!(state.blocks_this_session(Some(200), 800_070), 10);
+    }
+
+    #[test]
+    fn light_client_readiness_states() {
+        assert_eq!(
+            light_client_readiness(false, true, Some(true)),
+            "Misconfigured - filter index is disabled"
+        );
+        assert_eq!(
+            light_client_readiness(true, true, None),
+            "Waiting for filter index"
+        );
+        assert_eq!(
+            light_client_readiness(true, true, Some(false)),
+            "Building filter index"
+        );
+        assert_eq!(
+            light_client_readiness(true, true, Some(true)),
+            "Ready (serving peers)"
+        );
+        assert_eq!(
+            light_client_readiness(true, false, Some(true)),
+            "Ready (RPC only)"
+        );
+    }
+
+    #[test]
+    fn retained_blocks_window() {
+        assert_eq!(retained_blocks(840_000, 839_713), "288 (~2.0 days)");
+        assert_eq!(retained_blocks(840_000, 835_681), "4320 (~30.0 days)");
+        assert_eq!(retained_blocks(840_000, 840_000), "1 (~0.0 days)");
+    }
+
+    #[test]
+    fn listen_disabled_advertises_nothing() {
+        let onion = "abcdefghijklmnopqrstuvwxyzabcdefghijklmnopqrstuvwxyz2345.onion";
+        let outbound_only =
+            config("advanced: {peers: {listen: false, externalip: ['203.0.113.7']}}");
+        assert!(!listen_enabled(&outbound_only));
+        assert!(externalip_args(&outbound_only, onion).unwrap().is_empty());
+        assert_eq!(
+            p2p_listening(&outbound_only, &([127, 0, 0, 1], 8333).into()),
+            None
+        );
+        assert!(listen_enabled(&config("advanced: {peers: {}}")));
+    }
+
+    #[test]
+    fn header_lag_from_chaintips() {
+        let tips: Vec<ChainTip> = serde_json::from_str(
+            r#"[
+                {"height": 840010, "hash": "00aa", "branchlen": 10, "status": "headers-only"},
+                {"height": 840000, "hash": "00bb", "branchlen": 0, "status": "active"},
+                {"height": 840500, "hash": "00cc", "branchlen": 600, "status": "invalid"},
+                {"height": 839000, "hash": "00dd", "branchlen": 1, "status": "valid-fork"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(best_header(&tips).unwrap().hash, "00aa");
+        assert!(best_header(&tips[2..]).is_none());
+        assert_eq!(
+            time_lag(Some(1_713_571_767), Some(1_713_565_767)),
+            Some(6000)
+        );
+        // a header timestamp may be earlier than its parent's
+        assert_eq!(time_lag(Some(1_713_565_000), Some(1_713_565_767)), Some(0));
+        assert_eq!(time_lag(None, Some(1_713_565_767)), None);
+    }
+
+    #[test]
+    fn stats_socket_round_trip() {
+        use std::io::Read;
+
+        let path = test_dir("stats-socket").join("stats.sock");
+        // a socket left behind by a previous run is replaced
+        drop(bind_stats_socket(&path).unwrap());
+        let listener = bind_stats_socket(&path).unwrap();
+        let mut data = LinearMap::new();
+        data.insert(
+            Cow::from("Block Height"),
+            Stat {
+                value_type: "string",
+                value: "840000".to_owned(),
+                description: None,
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        *LATEST_STATS.lock().unwrap() = Some(Stats { version: 2, data });
+        std::thread::spawn(move || serve_stats(listener));
+        let mut received = String::new();
+        std::os::unix::net::UnixStream::connect(&path)
+            .unwrap()
+            .read_to_string(&mut received)
+            .unwrap();
+        let received: serde_json::Value = serde_json::from_str(&received).unwrap();
+        assert_eq!(received["version"], 2);
+        assert_eq!(received["data"]["Block Height"]["value"], "840000");
+        assert!(bind_stats_socket(Path::new("/nonexistent/dir/stats.sock")).is_err());
+    }
+
+    #[test]
+    fn parse_and_compare_versions() {
+        assert_eq!(parse_version("v28.1.0"), Some((28, 1, 0)));
+        assert_eq!(parse_version("28.1"), Some((28, 1, 0)));
+        assert_eq!(parse_version("v29.0.0rc1"), Some((29, 0, 0)));
+        assert_eq!(parse_version("v27.2.0-dirty\n"), Some((27, 2, 0)));
+        assert_eq!(parse_version("unknown"), None);
+        assert!(version_at_least("v28.1.0", "28.0"));
+        assert!(version_at_least("v28.0.0", "28.0"));
+        assert!(!version_at_least("v27.2.0", "28.0"));
+        assert!(version_at_least("v100.0.0", "28.0"));
+        assert!(!version_at_least("unknown", "0.1"));
+    }
+
+    #[test]
+    fn index_lag_status_from_getindexinfo() {
+        let index = |json: &str| -> IndexStatus { serde_json::from_str(json).unwrap() };
+        assert_eq!(
+            index_lag_status(
+                &index(r#"{"synced": true, "best_block_height": 840000}"#),
+                840000
+            ),
+            "Caught Up"
+        );
+        assert_eq!(
+            index_lag_status(
+                &index(r#"{"synced": false, "best_block_height": 210000}"#),
+                840000
+            ),
+            "Behind by 630000 blocks (25.00% indexed)"
+        );
+        // a new block arrived between getindexinfo and getblockchaininfo
+        assert_eq!(
+            index_lag_status(
+                &index(r#"{"synced": true, "best_block_height": 839999}"#),
+                840000
+            ),
+            "Caught Up"
+        );
     }
-    {
-        // disable chain data backup
-        let mut f = std::fs::File::create("/root/.bitcoin/.backupignore")?;
-        writeln!(f, "blocks/")?;
-        writeln!(f, "chainstate/")?;
-        writeln!(f, "indexes/")?;
-        writeln!(f, "testnet3/")?;
-        f.flush()?;
+
+    #[test]
+    fn debug_log_path_from_config() {
+        assert_eq!(
+            debug_log_path(&config("advanced: {network: testnet4, logging: {}}")),
+            Path::new("/root/.bitcoin/testnet4/debug.log")
+        );
+        assert_eq!(
+            debug_log_path(&config("advanced: {network: main, logging: {}}")),
+            Path::new("/root/.bitcoin/debug.log")
+        );
+        assert_eq!(
+            debug_log_path(&config(
+                "advanced: {network: testnet4, logging: {debuglogfile: logs/bitcoind.log}}"
+            )),
+            Path::new("/root/.bitcoin/logs/bitcoind.log")
+        );
     }
-    if reindex {
-        btc_args.push("-reindex".to_owned());
-        match fs::remove_file("/root/.bitcoin/requires.reindex") {
-            Ok(()) => (),
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
-            a => a?,
-        }
-    } else if reindex_chainstate {
-        btc_args.push("-reindex-chainstate".to_owned());
-        match fs::remove_file("/root/.bitcoin/requires.reindex_chainstate") {
-            Ok(()) => (),
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
-            a => a?,
-        }
+
+    #[test]
+    fn wallet_stats_are_summed_and_masked() {
+        let infos: Vec<WalletInfo> = vec![
+            serde_json::from_str(
+                r#"{"walletname": "default", "txcount": 12, "scanning": false, "descriptors": true}"#,
+            )
+            .unwrap(),
+            serde_json::from_str(
+                r#"{"walletname": "cold", "txcount": 30, "scanning": {"duration": 3720, "progress": 0.4213}}"#,
+            )
+            .unwrap(),
+            // older versions leave scanning out
+            serde_json::from_str(r#"{"walletname": "old", "txcount": 0}"#).unwrap(),
+        ];
+        let mut stats = LinearMap::new();
+        insert_wallet_stats(&mut stats, &infos);
+        let txs = stats.get("Wallet Transactions").unwrap();
+        assert_eq!(txs.value, "42");
+        assert!(txs.masked);
+        let rescan = stats.get("Wallet Rescan").unwrap();
+        assert_eq!(rescan.value, "42.13% (1h 2m elapsed)");
+        assert!(!rescan.masked);
+
+        let mut stats = LinearMap::new();
+        insert_wallet_stats(&mut stats, &infos[..1]);
+        assert!(stats.get("Wallet Rescan").is_none());
     }
 
-    std::io::copy(
-        &mut TemplatingReader::new(
-            std::fs::File::open("/mnt/assets/bitcoin.conf.template")?,
-            &config,
-            &"{{var}}".parse()?,
-            b'%',
-        ),
-        &mut std::fs::File::create("/root/.bitcoin/bitcoin.conf")?,
-    )?;
-    let mut child = std::process::Command::new("bitcoind")
-        .args(btc_args)
-        .spawn()?;
-    let raw_child = child.id();
-    *CHILD_PID.lock().unwrap() = Some(raw_child);
-    let pruned = {
-        config[&Value::from("advanced")][&Value::from("pruning")][&Value::from("mode")]
-            == "automatic"
-    };
-    let _proxy = if pruned {
-        let state = Arc::new(btc_rpc_proxy::State {
-            rpc_client: RpcClient::new("http://127.0.0.1:18332/".parse().unwrap()),
-            tor: Some(TorState {
-                proxy: format!("{}:9050", var("EMBASSY_IP")?).parse()?,
-                only: config[&Value::from("advanced")][&Value::from("peers")]
-                    [&Value::from("onlyonion")]
-                    .as_bool()
-                    .unwrap(),
-            }),
-            peer_timeout: Duration::from_secs(30),
-            peers: tokio::sync::RwLock::new(Arc::new(Peers::new())),
-            max_peer_age: Duration::from_secs(300),
-            max_peer_concurrency: Some(1),
+    #[test]
+    fn proxy_args_keep_onion_and_clearnet_apart() {
+        assert_eq!(
+            proxy_args(&config("advanced: {peers: {onion_proxy: '10.0.3.1:9050'}}")).unwrap(),
+            vec!["-onion=10.0.3.1:9050"]
+        );
+        assert_eq!(
+            proxy_args(&config(
+                "advanced: {peers: {onion_proxy: '10.0.3.1:9050', proxy: '192.168.1.20:1080'}}"
+            ))
+            .unwrap(),
+            vec!["-onion=10.0.3.1:9050", "-proxy=192.168.1.20:1080"]
+        );
+        // with clearnet disabled everything goes through Tor
+        assert_eq!(
+            proxy_args(&config(
+                "advanced: {peers: {onion_proxy: '10.0.3.1:9050', onlyonion: true}}"
+            ))
+            .unwrap(),
+            vec!["-onion=10.0.3.1:9050", "-proxy=10.0.3.1:9050"]
+        );
+        assert!(proxy_args(&config(
+            "advanced: {peers: {onion_proxy: '10.0.3.1:9050', proxy: 'proxy.lan'}}"
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn vm_rss_from_proc_status() {
+        let status = "Name:\tbitcoind\nState:\tS (sleeping)\nVmPeak:\t 3145728 kB\nVmRSS:\t  1572864 kB\nThreads:\t23\n";
+        assert_eq!(vm_rss_mib(status), Some(1536));
+        // kernel threads have no VmRSS line
+        assert_eq!(vm_rss_mib("Name:\tkthreadd\nState:\tS (sleeping)\n"), None);
+        let own = fs::read_to_string("/proc/self/status").unwrap();
+        assert!(vm_rss_mib(&own).is_some());
+    }
+
+    #[test]
+    fn reindex_marker_from_errors() {
+        let block = "Error: Corrupted block database detected.\nPlease restart with -reindex or -reindex-chainstate to recover.\n";
+        let chainstate = "Error: Error opening coins database: Unsupported chainstate database format found. Please restart with -reindex-chainstate. This will rebuild the chainstate database.\n";
+        let leveldb_chainstate = "Fatal LevelDB error: Corruption: block checksum mismatch: /root/.bitcoin/testnet4/chainstate/012345.ldb\n";
+        let leveldb_index = "Fatal LevelDB error: Corruption: bad entry in block: /root/.bitcoin/testnet4/blocks/index/000321.ldb\n";
+        assert_eq!(reindex_marker(block), Some("requires.reindex"));
+        assert_eq!(reindex_marker(leveldb_index), Some("requires.reindex"));
+        assert_eq!(
+            reindex_marker(chainstate),
+            Some("requires.reindex_chainstate")
+        );
+        assert_eq!(
+            reindex_marker(leveldb_chainstate),
+            Some("requires.reindex_chainstate")
+        );
+        assert_eq!(
+            reindex_marker(&format!("{}{}", leveldb_chainstate, leveldb_index)),
+            Some("requires.reindex")
+        );
+        assert_eq!(
+            reindex_marker("Error: Unable to bind to 0.0.0.0:8333 on this computer. Bitcoin Core is probably already running.\n"),
+            None
+        );
+        assert_eq!(reindex_marker("Error: Disk space is too low!\n"), None);
+    }
+
+    #[test]
+    fn mempool_fullness_percentages() {
+        let max = 300_000_000;
+        assert_eq!(mempool_fullness(0, max), "0% full");
+        assert_eq!(mempool_fullness(150_000_000, max), "50% full");
+        assert_eq!(mempool_fullness(269_999_999, max), "89% full");
+        assert_eq!(
+            mempool_fullness(270_000_000, max),
+            "90% full - low-fee transactions are being evicted"
+        );
+        // usage can briefly exceed the limit before bitcoind trims
+        assert_eq!(
+            mempool_fullness(310_000_000, max),
+            "100% full - low-fee transactions are being evicted"
+        );
+    }
+
+    #[test]
+    fn redaction_hides_secrets() {
+        let conf = "chain=testnet4\n[testnet4]\nrpcuser=bitcoin\nrpcpassword=hunter2\nrpcauth=alice:abc$def\nrpcworkqueue=128";
+        let redacted = redact_conf(conf);
+        assert_eq!(
+            redacted,
+            "chain=testnet4\n[testnet4]\nrpcuser=bitcoin\nrpcpassword=[REDACTED]\nrpcauth=[REDACTED]\nrpcworkqueue=128"
+        );
+        assert!(!redacted.contains("hunter2"));
+
+        let mut stats = serde_json::json!({
+            "version": 2,
+            "data": {
+                "RPC Password": {"type": "string", "value": "hunter2", "masked": true},
+                "Block Height": {"type": "string", "value": "840000", "masked": false},
+                "Tor Address": {"type": "string", "value": "abc.onion"},
+            },
         });
-        Some(std::thread::spawn(move || {
-            tokio::runtime::Runtime::new()
-                .unwrap()
-                .block_on(btc_rpc_proxy::main(state, ([0, 0, 0, 0], 48332).into()))
-                .unwrap();
+        redact_stats(&mut stats);
+        assert_eq!(stats["data"]["RPC Password"]["value"], REDACTED);
+        assert_eq!(stats["data"]["Block Height"]["value"], "840000");
+        assert_eq!(stats["data"]["Tor Address"]["value"], "abc.onion");
+    }
+
+    #[test]
+    fn historical_blocks_from_services() {
+        let services = |names: &[&str]| names.iter().map(|s| (*s).to_owned()).collect::<Vec<_>>();
+        assert_eq!(
+            historical_blocks_status(&services(&["NETWORK", "WITNESS", "NETWORK_LIMITED"])),
+            "Yes"
+        );
+        assert_eq!(
+            historical_blocks_status(&services(&["WITNESS", "NETWORK_LIMITED", "P2P_V2"])),
+            "No - pruned, serves recent blocks only"
+        );
+        assert_eq!(historical_blocks_status(&services(&["WITNESS"])), "No");
+    }
+
+    #[test]
+    fn stats_files_round_trip() {
+        let dir = test_dir("stats-files");
+        let mut data = LinearMap::new();
+        data.insert(
+            Cow::from("RPC Password"),
+            Stat {
+                value_type: "string",
+                value: "hunter2".to_owned(),
+                description: Some(Cow::from("The password for RPC connections")),
+                copyable: true,
+                qr: false,
+                masked: true,
+            },
+        );
+        let stats = Stats { version: 2, data };
+        let expected = serde_json::to_value(&stats).unwrap();
+
+        write_stats_files(&dir, "yaml", &stats).unwrap();
+        assert_eq!(read_stats_file(&dir, "yaml").unwrap(), expected);
+        assert!(!dir.join("stats.json").exists());
+
+        write_stats_files(&dir, "json", &stats).unwrap();
+        assert_eq!(read_stats_file(&dir, "json").unwrap(), expected);
+        // the yaml file would otherwise keep showing the last stats written before the switch
+        assert!(!dir.join("stats.yaml").exists());
+        assert!(read_stats_file(&dir, "yaml").is_err());
+
+        write_stats_files(&dir, "both", &stats).unwrap();
+        assert_eq!(read_stats_file(&dir, "yaml").unwrap(), expected);
+        assert_eq!(read_stats_file(&dir, "json").unwrap(), expected);
+    }
+
+    #[test]
+    fn record_rpc_keeps_slowest() {
+        let mut state = SidecarState::default();
+        state.record_rpc("getblockchaininfo", Duration::from_millis(40));
+        state.record_rpc("getpeerinfo", Duration::from_millis(250));
+        state.record_rpc("getmempoolinfo", Duration::from_millis(10));
+        // ties keep the call that was seen first
+        state.record_rpc("getnetworkinfo", Duration::from_millis(250));
+        assert_eq!(
+            state.slowest_rpc,
+            Some(("getpeerinfo".to_owned(), Duration::from_millis(250)))
+        );
+    }
+
+    #[test]
+    fn effective_assumevalid_reported_or_configured() {
+        let hash = "00000000000000000001a0a448d6cf2546b06801389cc030b2b18c6491266815";
+        assert_eq!(effective_assumevalid(Some(hash), None), hash);
+        assert_eq!(effective_assumevalid(Some(hash), Some("0")), hash);
+        assert_eq!(
+            effective_assumevalid(
+                Some("0000000000000000000000000000000000000000000000000000000000000000"),
+                None
+            ),
+            "Disabled"
+        );
+        // older versions don't report the block
+        assert_eq!(effective_assumevalid(None, Some("0")), "Disabled");
+        assert_eq!(
+            effective_assumevalid(
+                None,
+                Some("0000000000000000000000000000000000000000000000000000000000000000")
+            ),
+            "Disabled"
+        );
+        assert_eq!(
+            effective_assumevalid(None, Some(hash)),
+            format!("{} (configured)", hash)
+        );
+        assert_eq!(effective_assumevalid(None, None), "Bitcoin Core default");
+    }
+
+    #[test]
+    fn stall_restarts_are_bounded() {
+        let threshold = Duration::from_secs(30 * 60);
+        assert!(!should_restart_on_stall(
+            Duration::from_secs(60),
+            threshold,
+            0
+        ));
+        assert!(should_restart_on_stall(threshold, threshold, 0));
+        assert!(should_restart_on_stall(
+            threshold * 2,
+            threshold,
+            MAX_STALL_RESTARTS - 1
+        ));
+        // gives up instead of restarting in a loop
+        assert!(!should_restart_on_stall(
+            threshold * 2,
+            threshold,
+            MAX_STALL_RESTARTS
+        ));
+    }
+
+    #[test]
+    fn serve_ratio_without_downloads() {
+        assert_eq!(serve_ratio(0, 0), "No traffic yet");
+        assert_eq!(serve_ratio(1_000, 0), "Net serving");
+        assert_eq!(serve_ratio(3_000, 1_000), "3.00 (net serving)");
+        assert_eq!(serve_ratio(1_000, 4_000), "0.25 (net consuming)");
+    }
+
+    #[test]
+    fn blocksxor_version_gate_and_migration() {
+        assert_eq!(
+            blocksxor_args(Some(true), "v28.1.0", false),
+            (vec!["-blocksxor=1".to_owned()], None)
+        );
+        assert_eq!(
+            blocksxor_args(Some(false), "v28.1.0", false),
+            (vec!["-blocksxor=0".to_owned()], None)
+        );
+        // turning it off after a sync with obfuscation keeps the existing key
+        let (args, note) = blocksxor_args(Some(false), "v28.1.0", true);
+        assert!(args.is_empty());
+        assert!(note.unwrap().contains("already XOR-obfuscated"));
+        assert_eq!(
+            blocksxor_args(Some(true), "v28.1.0", true),
+            (vec!["-blocksxor=1".to_owned()], None)
+        );
+        // the default is silently left out on releases without the option
+        assert_eq!(
+            blocksxor_args(Some(true), "v27.2.0", false),
+            (Vec::new(), None)
+        );
+        assert_eq!(blocksxor_args(None, "v28.1.0", false), (Vec::new(), None));
+    }
+
+    #[test]
+    fn sync_rate_and_completion_estimate() {
+        let start = Instant::now();
+        let mut samples = std::collections::VecDeque::new();
+        assert_eq!(smoothed_sync_rate(&samples), None);
+        samples.push_back((start, 500_000));
+        assert_eq!(smoothed_sync_rate(&samples), None);
+        samples.push_back((start + Duration::from_secs(60), 500_300));
+        samples.push_back((start + Duration::from_secs(100), 500_500));
+        assert_eq!(smoothed_sync_rate(&samples), Some(5.0));
+        // no progress over the window
+        let stalled: std::collections::VecDeque<_> =
+            vec![(start, 500_000), (start + Duration::from_secs(60), 500_000)].into();
+        assert_eq!(smoothed_sync_rate(&stalled), None);
+
+        assert_eq!(
+            estimated_completion(1_700_000_000, 1_000, 5.0),
+            Some(1_700_000_200)
+        );
+        assert_eq!(estimated_completion(1_700_000_000, 1_000, 0.0), None);
+    }
+
+    #[test]
+    fn backupignore_per_chain() {
+        let ignore = vec!["blocks".to_owned(), "indexes/txindex/".to_owned()];
+        assert_eq!(
+            backupignore("testnet4", &ignore),
+            "blocks/\nchainstate/\nindexes/\nmempool.dat\n\
+             testnet3/blocks/\ntestnet3/chainstate/\ntestnet3/indexes/\ntestnet3/mempool.dat\n\
+             testnet4/blocks/\ntestnet4/indexes/txindex/\ntestnet4/mempool.dat\n\
+             signet/blocks/\nsignet/chainstate/\nsignet/indexes/\nsignet/mempool.dat\n\
+             regtest/blocks/\nregtest/chainstate/\nregtest/indexes/\nregtest/mempool.dat\n"
+        );
+        let out = backupignore("main", &[]);
+        assert!(out.starts_with("mempool.dat\ntestnet3/blocks/\n"));
+        // wallets of every network stay in the backup
+        assert!(!out.lines().any(|line| line.contains("wallets")
+            || ["testnet3/", "testnet4/", "signet/", "regtest/"].contains(&line)));
+    }
+
+    #[test]
+    fn rpc_auth_status_from_cli_output() {
+        let output = |code: i32, stderr: &str| Output {
+            status: std::process::ExitStatus::from_raw(code << 8),
+            stdout: Vec::new(),
+            stderr: stderr.as_bytes().to_vec(),
+        };
+        assert_eq!(rpc_auth_status(&output(0, "")), Some(true));
+        assert_eq!(
+            rpc_auth_status(&output(
+                1,
+                "error: Authorization failed: Incorrect rpcuser or rpcpassword"
+            )),
+            Some(false)
+        );
+        // unreachable or still starting up says nothing about the credentials
+        assert_eq!(
+            rpc_auth_status(&output(
+                1,
+                "error: Could not connect to the server 127.0.0.1:18443"
+            )),
+            None
+        );
+        assert_eq!(
+            rpc_auth_status(&output(
+                28,
+                "error code: -28\nerror message:\nLoading block index…"
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn softfork_stats_from_deploymentinfo() {
+        // getdeploymentinfo on regtest while testdummy is signaling
+        let info: DeploymentInfo = serde_json::from_str(
+            r#"{
+                "hash": "2ea3f2c9c3e4b3d3bdb6c53a3d6d6b6e0a0e1f0c5a0f3c9e3e7e8d1c3b1a0f9e",
+                "height": 200,
+                "deployments": {
+                    "bip34": {"type": "buried", "active": true, "height": 1},
+                    "segwit": {"type": "buried", "active": true, "height": 0},
+                    "testdummy": {
+                        "type": "bip9",
+                        "active": false,
+                        "bip9": {
+                            "start_time": 0,
+                            "timeout": 9223372036854775807,
+                            "min_activation_height": 0,
+                            "status": "started",
+                            "bit": 28,
+                            "since": 144,
+                            "status_next": "started",
+                            "statistics": {
+                                "period": 144,
+                                "threshold": 108,
+                                "elapsed": 56,
+                                "count": 14,
+                                "possible": true
+                            },
+                            "signalling": "--#-#-----"
+                        }
+                    },
+                    "taproot": {
+                        "type": "bip9",
+                        "height": 0,
+                        "active": true,
+                        "bip9": {
+                            "start_time": -1,
+                            "timeout": 9223372036854775807,
+                            "min_activation_height": 0,
+                            "status": "active",
+                            "since": 0,
+                            "status_next": "active"
+                        }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let mut stats = LinearMap::new();
+        insert_softfork_stats(&mut stats, info.deployments.clone(), 200);
+        assert_eq!(stats.get("Testdummy Status").unwrap().value, "Started");
+        assert_eq!(
+            stats.get("Testdummy Start Time").unwrap().value,
+            "01/01/1970 @ 00:00:00"
+        );
+        assert_eq!(stats.get("Testdummy Timeout").unwrap().value, "Never");
+        assert_eq!(
+            stats.get("Testdummy Signal Percentage").unwrap().value,
+            "25.00%"
+        );
+        // recently activated deployments are still shown
+        assert_eq!(
+            stats.get("Taproot Start Time").unwrap().value,
+            "Always Active"
+        );
+        assert!(stats.get("Bip34 Status").is_none());
+
+        // ~12 weeks after activation taproot is no longer of interest
+        let mut stats = LinearMap::new();
+        insert_softfork_stats(&mut stats, info.deployments, 12096);
+        assert!(stats.get("Taproot Status").is_none());
+        assert!(stats.get("Testdummy Status").is_some());
+    }
+
+    #[test]
+    fn rpc_url_with_and_without_tls() {
+        assert_eq!(rpc_url(true, "node.local", 443), "https://node.local");
+        assert_eq!(rpc_url(true, "node.local", 8332), "https://node.local:8332");
+        assert_eq!(rpc_url(false, "abc.onion", 80), "http://abc.onion");
+        assert_eq!(rpc_url(false, "abc.onion", 8332), "http://abc.onion:8332");
+        // default ports only apply to their own scheme
+        assert_eq!(rpc_url(false, "node.local", 443), "http://node.local:443");
+        assert_eq!(rpc_url(true, "node.local", 80), "https://node.local:80");
+    }
+
+    #[test]
+    fn user_agent_shown_verbatim() {
+        let info = network_info(serde_json::json!({
+            "version": 280100,
+            "subversion": "/Satoshi:28.1.0(start9)/",
+            "connections": 10,
+            "connections_in": 0,
+            "connections_out": 10,
+        }));
+        let stat = user_agent_stat(&info);
+        assert_eq!(stat.value, "/Satoshi:28.1.0(start9)/");
+        assert!(stat.copyable);
+        assert!(!stat.masked);
+    }
+
+    #[test]
+    fn mempool_evicting_heuristic() {
+        let info = |usage: u64, minfee: f64| -> MempoolInfo {
+            serde_json::from_value(serde_json::json!({
+                "loaded": true,
+                "size": 120000,
+                "bytes": 100000000,
+                "usage": usage,
+                "maxmempool": 300000000,
+                "mempoolminfee": minfee,
+                "minrelaytxfee": 0.00001,
+            }))
+            .unwrap()
+        };
+        // full and the minimum fee went up since the last poll
+        assert!(mempool_evicting(&info(295_000_000, 0.00002), Some(0.00001)));
+        // full but the minimum fee held steady
+        assert!(!mempool_evicting(
+            &info(295_000_000, 0.00002),
+            Some(0.00002)
+        ));
+        // the minimum fee rose but there is plenty of room
+        assert!(!mempool_evicting(
+            &info(100_000_000, 0.00002),
+            Some(0.00001)
+        ));
+        // nothing to compare against on the first poll
+        assert!(!mempool_evicting(&info(295_000_000, 0.00002), None));
+    }
+
+    #[test]
+    fn corruption_detected_in_logged_output() {
+        let stdout = "2026-10-17T12:00:00Z Bitcoin Core version v28.1.0 (release build)\n\
+            2026-10-17T12:00:01Z Opening LevelDB in /root/.bitcoin/testnet4/chainstate\n\
+            2026-10-17T12:00:02Z Fatal LevelDB error: Corruption: block checksum mismatch: /root/.bitcoin/testnet4/chainstate/004321.ldb\n\
+            2026-10-17T12:00:02Z You can use -debug=leveldb to get more complete diagnostic messages\n";
+        let tail = forward_output(stdout.as_bytes());
+        assert_eq!(tail.lines().count(), 4);
+        assert_eq!(reindex_marker(&tail), Some("requires.reindex_chainstate"));
+
+        let long = (0..OUTPUT_TAIL_LINES + 50)
+            .map(|i| format!("line {}\n", i))
+            .collect::<String>();
+        let tail = forward_output(long.as_bytes());
+        assert_eq!(tail.lines().count(), OUTPUT_TAIL_LINES);
+        assert!(tail.starts_with("line 50\n"));
+
+        // invalid UTF-8 and CRLF endings don't stop the rest being forwarded
+        let mixed = b"Loaded wallet \xff\xfe\r\nUpdateTip: new best\nno trailing newline";
+        let tail = forward_output(&mixed[..]);
+        assert_eq!(
+            tail,
+            "Loaded wallet \u{fffd}\u{fffd}\nUpdateTip: new best\nno trailing newline"
+        );
+    }
+
+    #[test]
+    fn rescan_command_args() {
+        let config = config("{rpc: {enable: true}, advanced: {network: testnet4}}");
+        let from_height = args(&rescan_command(&config, "my wallet", Some(120_000)));
+        assert!(from_height.contains(&"-rpcclienttimeout=0".to_owned()));
+        assert!(from_height.ends_with(&[
+            "-rpcwallet=my wallet".to_owned(),
+            "rescanblockchain".to_owned(),
+            "120000".to_owned(),
+        ]));
+        let from_genesis = args(&rescan_command(&config, "default", None));
+        assert_eq!(from_genesis.last().unwrap(), "rescanblockchain");
+    }
+
+    #[test]
+    fn only_one_rescan_at_a_time() {
+        let lock = test_dir("rescan-lock").join("rescan.lock");
+        assert!(acquire_rescan_lock(&lock).unwrap());
+        assert_eq!(
+            fs::read_to_string(&lock).unwrap(),
+            std::process::id().to_string()
+        );
+        // held by this still running process
+        assert!(!acquire_rescan_lock(&lock).unwrap());
+        // left behind by a rescan that is gone
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        fs::write(&lock, child.id().to_string()).unwrap();
+        assert!(acquire_rescan_lock(&lock).unwrap());
+        fs::write(&lock, "").unwrap();
+        assert!(acquire_rescan_lock(&lock).unwrap());
+    }
+
+    fn chain_info(blocks: usize, headers: usize, verificationprogress: f64) -> ChainInfo {
+        serde_json::from_value(serde_json::json!({
+            "chain": "testnet4",
+            "blocks": blocks,
+            "headers": headers,
+            "bestblockhash": "00",
+            "verificationprogress": verificationprogress,
+            "size_on_disk": 1_000_000,
         }))
-    } else {
-        None
-    };
-    let _sidecar_handle = std::thread::spawn(move || loop {
-        sidecar(&config, &rpc_addr)
-            .err()
-            .map(|e| eprintln!("ERROR IN SIDECAR: {}", e));
-        std::thread::sleep(sidecar_poll_interval);
-    });
-    let child_res = child.wait()?;
-    let code = if let Some(code) = child_res.code() {
-        code
-    } else if let Some(signal) = child_res.signal() {
-        eprintln!(
-            "PROCESS TERMINATED BY {}",
-            Signal::try_from(signal)
-                .map(|s| s.to_string())
-                .unwrap_or_else(|_| "UNKNOWN SIGNAL".to_owned())
+        .unwrap()
+    }
+
+    fn peers(json: serde_json::Value) -> Vec<PeerInfo> {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn sync_phase_follows_headers_then_blocks() {
+        let connected = peers(serde_json::json!([
+            {"startingheight": 80_000, "network": "onion", "connection_type": "outbound-full-relay"},
+            {"startingheight": 80_002, "network": "ipv4", "connection_type": "outbound-full-relay"},
+            {"startingheight": -1, "network": "onion", "connection_type": "inbound"},
+        ]));
+        // a fresh node first fetches headers while blocks stay at 0
+        let info = chain_info(0, 30_000, 0.0);
+        let remaining = headers_remaining(&connected, &info);
+        assert_eq!(remaining, Some(50_002));
+        assert_eq!(sync_phase(&info, remaining), "Syncing Headers");
+        let info = chain_info(40_000, 80_002, 0.3);
+        let remaining = headers_remaining(&connected, &info);
+        assert_eq!(remaining, Some(0));
+        assert_eq!(sync_phase(&info, remaining), "Downloading Blocks");
+        let info = chain_info(80_002, 80_002, 1.0);
+        assert_eq!(
+            sync_phase(&info, headers_remaining(&connected, &info)),
+            "Synced"
         );
-        128 + signal
-    } else {
-        1
-    };
+        // without peer heights the phase comes from blocks and headers alone
+        let unknown = peers(serde_json::json!([{"startingheight": -1}]));
+        assert_eq!(headers_remaining(&unknown, &info), None);
+        assert_eq!(
+            sync_phase(&chain_info(100, 200, 0.1), None),
+            "Downloading Blocks"
+        );
+    }
 
-    std::process::exit(code)
-}
+    #[test]
+    fn adaptive_par_from_load() {
+        assert_eq!(load_avg_1m("5.50 3.20 1.10 4/812 31337\n"), Some(5.5));
+        // below 75% of the cores the configured par is kept
+        assert_eq!(adaptive_par(2.9, 4), None);
+        assert_eq!(adaptive_par(3.0, 4), Some(1));
+        assert_eq!(adaptive_par(6.5, 8), Some(1));
+        assert_eq!(adaptive_par(12.0, 16), Some(4));
+        // never fewer than one thread, even when overloaded
+        assert_eq!(adaptive_par(20.0, 4), Some(1));
+        assert_eq!(adaptive_par(1.0, 0), None);
+    }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    env_logger::Builder::from_env(Env::default().default_filter_or("warn")).init();
-    let reindex = Path::new("/root/.bitcoin/requires.reindex").exists();
-    let reindex_chainstate = Path::new("/root/.bitcoin/requires.reindex_chainstate").exists();
-    ctrlc::set_handler(move || {
-        if let Some(raw_child) = *CHILD_PID.lock().unwrap() {
-            use nix::{
-                sys::signal::{kill, SIGTERM},
-                unistd::Pid,
-            };
-            kill(Pid::from_raw(raw_child as i32), SIGTERM).unwrap();
-        } else {
-            std::process::exit(143)
+    #[test]
+    fn download_and_sync_progress_differ() {
+        // early blocks are nearly empty, so half the blocks are a fraction of the work
+        let info = chain_info(50_000, 100_000, 0.0123);
+        assert_eq!(download_progress(&info), "50.00%");
+        assert_eq!(sync_progress(&info), "1.23%");
+        // near the tip verificationprogress rounds up while blocks are still missing
+        let info = chain_info(99_990, 100_000, 0.99999);
+        assert_eq!(download_progress(&info), "99.99%");
+        assert_eq!(sync_progress(&info), "100.00%");
+        let synced = chain_info(100_000, 100_000, 0.9999987);
+        assert_eq!(download_progress(&synced), "100%");
+        assert_eq!(sync_progress(&synced), "100%");
+    }
+
+    #[test]
+    fn onion_only_peer_warning_conditions() {
+        let warning = onion_only_peer_warning(true, Some(8), 0).unwrap();
+        assert!(warning.contains("maxconnections of 8"));
+        assert!(warning.contains(&MIN_ONION_ONLY_CONNECTIONS.to_string()));
+        assert_eq!(
+            onion_only_peer_warning(true, Some(MIN_ONION_ONLY_CONNECTIONS), 0),
+            None
+        );
+        // known peers to connect to make up for the few slots
+        assert_eq!(onion_only_peer_warning(true, Some(8), 2), None);
+        assert_eq!(onion_only_peer_warning(false, Some(8), 0), None);
+        // bitcoind's default of 125 is plenty
+        assert_eq!(onion_only_peer_warning(true, None, 0), None);
+    }
+
+    #[test]
+    fn recent_feerates_average_percentiles() {
+        assert_eq!(recent_feerates(&[]), None);
+        let blocks: Vec<[f64; 5]> = [
+            r#"{"feerate_percentiles": [2, 3, 5, 8, 20], "height": 840000}"#,
+            r#"{"feerate_percentiles": [4, 5, 9, 12, 41], "height": 840001}"#,
+        ]
+        .iter()
+        .map(|json| {
+            serde_json::from_str::<BlockStats>(json)
+                .unwrap()
+                .feerate_percentiles
+        })
+        .collect();
+        assert_eq!(recent_feerates(&blocks).unwrap(), "3.0 / 7.0 / 30.5 sat/vB");
+    }
+
+    #[test]
+    fn failed_stats_writes_back_off_quietly() {
+        let mut state = SidecarState {
+            poll_interval: Duration::from_secs(5),
+            ..Default::default()
+        };
+        let failed = || -> Result<(), Box<dyn Error>> { Err("No space left on device".into()) };
+        assert!(state
+            .record_stats_write(failed())
+            .unwrap()
+            .contains("No space left on device"));
+        assert_eq!(state.next_poll_delay(), Duration::from_secs(10));
+        for _ in 0..10 {
+            assert_eq!(state.record_stats_write(failed()), None);
         }
-    })?;
-    inner_main(reindex, reindex_chainstate)
-}
+        assert_eq!(state.stats_write_failures, 11);
+        assert_eq!(
+            state.next_poll_delay(),
+            Duration::from_secs(5) * (1 << MAX_WRITE_BACKOFF_DOUBLINGS)
+        );
+        assert_eq!(
+            state.record_stats_write(Ok(())).unwrap(),
+            "Writing stats succeeded again after 11 failed attempts"
+        );
+        assert_eq!(state.next_poll_delay(), Duration::from_secs(5));
+        assert_eq!(state.record_stats_write(Ok(())), None);
+    }
 
-fn human_readable_timestamp(unix_time: u64) -> String {
-    chrono::DateTime::<chrono::Utc>::from(
-        std::time::UNIX_EPOCH + std::time::Duration::from_secs(unix_time),
-    )
-    .format("%m/%d/%Y @ %H:%M:%S")
-    .to_string()
+    #[test]
+    fn parse_total_unspendable_amount() {
+        // gettxoutsetinfo muhash, served by coinstatsindex
+        let info: TxOutSetInfo = serde_json::from_str(
+            r#"{
+                "height": 840000,
+                "bestblock": "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5",
+                "txouts": 176946140,
+                "bogosize": 13190349472,
+                "muhash": "e3ba0f5fe8f3e0f8b6c4d1e3f0c9bb1d7e1e2e7f7b0bde0c7a0f1f6b9d8b5a3c",
+                "total_amount": 19687500.0,
+                "total_unspendable_amount": 220.96495696,
+                "block_info": {"prevout_spent": 0, "coinbase": 3.125, "unspendable": 0}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(info.total_unspendable_amount, Some(220.96495696));
+        // without coinstatsindex there is no such total
+        let info: TxOutSetInfo = serde_json::from_str(
+            r#"{"height": 840000, "txouts": 176946140, "disk_size": 11811160064, "total_amount": 19687500.0}"#,
+        )
+        .unwrap();
+        assert_eq!(info.total_unspendable_amount, None);
+    }
+
+    #[test]
+    fn transient_vs_permanent_startup_failures() {
+        assert!(transient_startup_failure(
+            "Error: Cannot obtain a lock on data directory /root/.bitcoin. Bitcoin Core is probably already running."
+        ));
+        assert!(transient_startup_failure(
+            "2026-10-17T12:00:00Z [error] Unable to bind to 0.0.0.0:8333 on this computer. Bitcoin Core is probably already running.\nError: Failed to listen on any port."
+        ));
+        assert!(transient_startup_failure(
+            "Error: Unable to start HTTP server. See debug log for details."
+        ));
+        assert!(!transient_startup_failure(
+            "Error: Invalid combination of -txindex and -prune"
+        ));
+        assert!(!transient_startup_failure(
+            "Error: Corrupted block database detected."
+        ));
+        assert!(!transient_startup_failure(""));
+    }
+
+    #[test]
+    fn block_relay_only_status_counts_only_that_connection_type() {
+        let connected = peers(serde_json::json!([
+            {"connection_type": "block-relay-only"},
+            {"connection_type": "outbound-full-relay"},
+            {"connection_type": "block-relay-only"},
+            {"connection_type": "inbound"},
+            {"connection_type": "feeler"}
+        ]));
+        assert_eq!(block_relay_only_status(&connected), "2");
+        let without = peers(serde_json::json!([
+            {"connection_type": "outbound-full-relay"},
+            {"connection_type": "inbound"}
+        ]));
+        assert_eq!(
+            block_relay_only_status(&without),
+            "0 (reduced eclipse attack resistance)"
+        );
+        assert_eq!(
+            block_relay_only_status(&[]),
+            "0 (reduced eclipse attack resistance)"
+        );
+    }
+
+    #[test]
+    fn legacy_onion_addresses_flags_only_v2() {
+        let v2 = "expyuzz4wqqyqhjn.onion";
+        let v3 = "2gzyxa5ihm7nsggfxnu52rck2vv4rvmdlkiu3zzui5du4xyclen53wid.onion";
+        assert_eq!(onion_version(v2), Some(2));
+        assert_eq!(onion_version(&format!("{}:8333", v2)), Some(2));
+        assert_eq!(onion_version(v3), Some(3));
+        assert_eq!(onion_version(&format!("{}:8333", v3)), Some(3));
+        assert_eq!(onion_version("short.onion"), None);
+        assert_eq!(onion_version("203.0.113.5:8333"), None);
+
+        let conf = config(&format!(
+            "{{advanced: {{peers: {{externalip: ['{v2}', '{v3}'], addnode: [{{hostname: '{v2}', port: 8333}}, {{hostname: '{v3}'}}, {{hostname: '203.0.113.5'}}]}}}}}}",
+            v2 = v2,
+            v3 = v3
+        ));
+        assert_eq!(
+            legacy_onion_addresses(&conf, &format!("{}:8333", v3)),
+            vec![v2.to_owned(), v2.to_owned()]
+        );
+        assert_eq!(
+            legacy_onion_addresses(&conf, &format!("{}:8333", v2)),
+            vec![format!("{}:8333", v2), v2.to_owned(), v2.to_owned()]
+        );
+        assert!(legacy_onion_addresses(&config("{}"), v3).is_empty());
+    }
+
+    #[test]
+    fn dbcache_usage_from_update_tip() {
+        let log = "\
+2026-10-17T12:00:00Z UpdateTip: new best=0000 height=800000 version=0x20000000 log2_work=94.4 tx=880000000 date='2023-07-24T03:17:05Z' progress=0.990000 cache=214.2MiB(1560042txo)
+2026-10-17T12:00:01Z Saw new header hash=0000 height=800002
+2026-10-17T12:00:02Z UpdateTip: new best=0001 height=800001 version=0x20000000 log2_work=94.4 tx=880003000 date='2023-07-24T03:27:05Z' progress=0.990001 cache=225.0MiB(1600000txo)
+2026-10-17T12:00:03Z New outbound peer connected
+";
+        assert_eq!(coins_cache_mib(log), Some(225.0));
+        assert_eq!(
+            coins_cache_mib("2026-10-17T12:00:00Z Bitcoin Core version v27.0\n"),
+            None
+        );
+        assert_eq!(coins_cache_mib(""), None);
+        assert_eq!(format_dbcache(450, Some(225.0)), "225.0 / 450 MiB (50%)");
+        assert_eq!(format_dbcache(450, None), "450 MiB");
+    }
+
+    #[test]
+    fn pidfile_written_and_removed() {
+        let dir = test_dir("pidfile");
+        let pidfile = dir.join("bitcoind.pid");
+        *PIDFILE.lock().unwrap() = Some(pidfile.clone());
+        write_pidfile(4242);
+        assert_eq!(fs::read_to_string(&pidfile).unwrap(), "4242\n");
+        remove_pidfile();
+        assert!(!pidfile.exists());
+        // already gone, e.g. after a second shutdown signal
+        remove_pidfile();
+        *PIDFILE.lock().unwrap() = None;
+        write_pidfile(4242);
+        assert!(!pidfile.exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn stats_flagged_stale_after_missed_polls() {
+        let poll_interval = Duration::from_secs(5);
+        let limit = 5 * LOADED_POLL_MULTIPLIER as u64 * STALE_STATS_POLLS as u64;
+        assert!(!stats_stale(0, poll_interval));
+        // a sidecar backing off under load is still healthy
+        assert!(!stats_stale(
+            5 * LOADED_POLL_MULTIPLIER as u64,
+            poll_interval
+        ));
+        assert!(!stats_stale(limit, poll_interval));
+        assert!(stats_stale(limit + 1, poll_interval));
+
+        let mut data = LinearMap::new();
+        data.insert(
+            Cow::from("Stats Last Updated"),
+            Stat {
+                value_type: "string",
+                value: human_readable_timestamp(1_700_000_000),
+                description: None,
+                copyable: false,
+                qr: false,
+                masked: false,
+            },
+        );
+        let stale = stale_stats(&Stats { version: 2, data }, 1_700_000_000);
+        assert_eq!(
+            stale.data["Stats Last Updated"].value,
+            format!("{} (stale)", human_readable_timestamp(1_700_000_000))
+        );
+    }
+
+    #[test]
+    fn acceptnonstdtxn_only_on_test_chains() {
+        assert!(acceptnonstdtxn_args("main", true).is_err());
+        assert!(acceptnonstdtxn_args("main", false).unwrap().is_empty());
+        for chain in &["test", "testnet4", "signet", "regtest"] {
+            assert_eq!(
+                acceptnonstdtxn_args(chain, true).unwrap(),
+                vec!["-acceptnonstdtxn=1"]
+            );
+            assert!(acceptnonstdtxn_args(chain, false).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn discoverability_requires_listening_advertising_and_serving() {
+        let full = vec!["NETWORK".to_owned(), "WITNESS".to_owned()];
+        let pruned = vec!["NETWORK_LIMITED".to_owned(), "WITNESS".to_owned()];
+        let blocksonly = vec!["WITNESS".to_owned()];
+        assert_eq!(discoverability(true, 1, &full), "Yes");
+        assert_eq!(discoverability(true, 2, &pruned), "Yes");
+        assert_eq!(
+            discoverability(false, 1, &full),
+            "No - not accepting connections"
+        );
+        assert_eq!(
+            discoverability(true, 0, &full),
+            "No - no address advertised"
+        );
+        assert_eq!(
+            discoverability(true, 1, &blocksonly),
+            "No - not serving blocks"
+        );
+        assert_eq!(
+            discoverability(false, 0, &[]),
+            "No - not accepting connections"
+        );
+    }
+
+    #[test]
+    fn test_peers_seed_regtest_only() {
+        assert!(test_peer_args("regtest", None).unwrap().is_empty());
+        assert!(test_peer_args("main", None).unwrap().is_empty());
+        assert_eq!(
+            test_peer_args("regtest", Some("127.0.0.1:18444,node1:18444,")).unwrap(),
+            vec![
+                "-connect=127.0.0.1:18444",
+                "-connect=node1:18444",
+                "-dnsseed=0",
+                "-fixedseeds=0"
+            ]
+        );
+        // no peers at all still keeps the node off the real network
+        assert_eq!(
+            test_peer_args("regtest", Some("")).unwrap(),
+            vec!["-dnsseed=0", "-fixedseeds=0"]
+        );
+        for chain in &["main", "test", "testnet4", "signet"] {
+            assert!(test_peer_args(chain, Some("127.0.0.1:18444")).is_err());
+        }
+        assert!(test_peer_args("regtest", Some("127.0.0.1 -rpcallowip=0.0.0.0/0")).is_err());
+    }
+
+    #[test]
+    fn niceness_range_checked() {
+        assert_eq!(
+            niceness(&config("advanced: {performance: {}}")).unwrap(),
+            None
+        );
+        assert_eq!(
+            niceness(&config("advanced: {performance: {nice: 10}}")).unwrap(),
+            Some(10)
+        );
+        assert_eq!(
+            niceness(&config("advanced: {performance: {nice: -20}}")).unwrap(),
+            Some(-20)
+        );
+        assert_eq!(
+            niceness(&config("advanced: {performance: {nice: 19}}")).unwrap(),
+            Some(19)
+        );
+        assert!(niceness(&config("advanced: {performance: {nice: 20}}")).is_err());
+        assert!(niceness(&config("advanced: {performance: {nice: -21}}")).is_err());
+        assert!(niceness(&config("advanced: {performance: {nice: 4294967306}}")).is_err());
+    }
+
+    #[test]
+    fn zmq_topics_under_master_switch() {
+        assert!(zmq_args(&config(
+            "{zmq-enabled: false, advanced: {zmq: {rawblock: true}}}"
+        ))
+        .is_empty());
+        assert!(zmq_args(&config("advanced: {zmq: {rawblock: true}}")).is_empty());
+        // nothing picked keeps publishing everything dependent services rely on
+        assert_eq!(
+            zmq_args(&config("{zmq-enabled: true, advanced: {}}")),
+            vec![
+                "-zmqpubrawblock=tcp://0.0.0.0:28332",
+                "-zmqpubhashblock=tcp://0.0.0.0:28332",
+                "-zmqpubrawtx=tcp://0.0.0.0:28333",
+                "-zmqpubhashtx=tcp://0.0.0.0:28333",
+                "-zmqpubsequence=tcp://0.0.0.0:28333",
+            ]
+        );
+        assert_eq!(
+            zmq_args(&config(
+                "{zmq-enabled: true, advanced: {zmq: {rawblock: true, hashblock: false, rawtx: false, hashtx: true}}}"
+            )),
+            vec![
+                "-zmqpubrawblock=tcp://0.0.0.0:28332",
+                "-zmqpubhashtx=tcp://0.0.0.0:28333",
+            ]
+        );
+    }
 }
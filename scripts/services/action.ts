@@ -1,4 +1,26 @@
-import { util, types as T } from "../dependencies.ts";
+import { util, types as T, YAML } from "../dependencies.ts";
+
+// Data subdirectory of each network, matching CHAINS in the manager
+const CHAIN_DIRS: Record<string, string> = {
+  main: "",
+  test: "testnet3/",
+  testnet4: "testnet4/",
+  signet: "signet/",
+  regtest: "regtest/",
+};
+
+async function chainDir(effect: T.Effects): Promise<string> {
+  const config = await effect
+    .readFile({
+      path: "start9/config.yaml",
+      volumeId: "main",
+    })
+    .catch(() => null);
+  const network = config
+    ? (YAML.parse(config) as any)?.advanced?.network
+    : undefined;
+  return CHAIN_DIRS[network ?? "testnet4"] ?? CHAIN_DIRS.testnet4;
+}
 
 export const action = {
   async "delete-txindex"(
@@ -6,7 +28,7 @@ export const action = {
     _input?: T.Config
   ): Promise<T.ResultType<T.ActionResult>> {
     const txinfoLocation = {
-      path: "testnet4/indexes/txindex",
+      path: `${await chainDir(effect)}indexes/txindex`,
       volumeId: "main",
     };
     if ((await util.exists(effect, txinfoLocation)) === false) {
@@ -34,7 +56,7 @@ export const action = {
     _input?: T.Config
   ): Promise<T.ResultType<T.ActionResult>> {
     const peersLocation = {
-      path: "testnet4/peers.dat",
+      path: `${await chainDir(effect)}peers.dat`,
       volumeId: "main",
     };
     if ((await util.exists(effect, peersLocation)) === false) {
@@ -62,7 +84,7 @@ export const action = {
     _input?: T.Config,
   ): Promise<T.ResultType<T.ActionResult>> {
     const coinstatsinfoLocation = {
-      path: "indexes/coinstats",
+      path: `${await chainDir(effect)}indexes/coinstats`,
       volumeId: "main",
     };
     if (await util.exists(effect, coinstatsinfoLocation) === false) {
@@ -22,6 +22,15 @@ export const getConfig: T.ExpectedExports.getConfig = async (effects) => {
       target: "tor-address",
       interface: "rpc",
     },
+    "zmq-tor-address": {
+      name: "ZeroMQ Tor Address",
+      description: "The Tor address of the ZeroMQ interface",
+      type: "pointer",
+      subtype: "package",
+      "package-id": "bitcoind-testnet",
+      target: "tor-address",
+      interface: "zmq",
+    },
     rpc: {
       type: "object",
       name: "RPC Settings",
@@ -83,6 +92,24 @@ export const getConfig: T.ExpectedExports.getConfig = async (effects) => {
               },
               range: "[0,*)",
             },
+            lan_port: {
+              name: "LAN Port",
+              description:
+                "The port RPC is advertised on for the local network in the Quick Connect URL and LAN RPC URL. Only change this if RPC is forwarded to a different port.",
+              type: "number",
+              nullable: false,
+              range: "[1,65535]",
+              integral: true,
+              units: undefined,
+              default: 443,
+            },
+            lan_tls: {
+              type: "boolean",
+              name: "LAN TLS",
+              description:
+                "Whether RPC is reached over TLS on the local network. StartOS terminates TLS for the default LAN port.",
+              default: true,
+            },
             servertimeout: {
               name: "Rpc Server Timeout",
               description:
@@ -123,7 +150,7 @@ export const getConfig: T.ExpectedExports.getConfig = async (effects) => {
     "zmq-enabled": {
       type: "boolean",
       name: "ZeroMQ Enabled",
-      description: "The ZeroMQ interface is useful for some applications which might require data related to block and transaction events from Bitcoin Core. For example, LND requires ZeroMQ be enabled for LND to get the latest block data",
+      description: "The ZeroMQ interface is useful for some applications which might require data related to block and transaction events from Bitcoin Core. For example, LND requires ZeroMQ be enabled for LND to get the latest block data. Individual topics can be chosen under Advanced > ZeroMQ Topics",
       default: true,
     },
     txindex: {
@@ -167,6 +194,36 @@ export const getConfig: T.ExpectedExports.getConfig = async (effects) => {
           integral: false,
           units: "BTC/kB",
         },
+        walletdir: {
+          name: "Wallet Directory",
+          description:
+            "Store wallets in this directory, relative to the Bitcoin data directory. It is created if it does not exist. Leave blank to use the default location.",
+          type: "string",
+          nullable: true,
+          pattern: "^[a-zA-Z0-9_-]+(/[a-zA-Z0-9_-]+)*$",
+          "pattern-description":
+            "Must be a relative path made of letters, numbers, underscores and dashes.",
+        },
+        fallbackfee: {
+          name: "Fallback Fee",
+          description:
+            "The fee rate the wallet uses when fee estimation has insufficient data, which is common on test networks. Leave blank to disable the fallback, in which case sending fails until estimates are available.",
+          type: "number",
+          nullable: true,
+          range: "[0,*)",
+          integral: false,
+          units: "BTC/kvB",
+        },
+        maxtxfee: {
+          name: "Max Transaction Fee",
+          description:
+            "The maximum total fee (in BTC) the wallet will pay for a single transaction. Transactions exceeding this fee are refused, protecting against accidental overpayment. Leave blank to use the Bitcoin Core default.",
+          type: "number",
+          nullable: true,
+          range: "(0,*)",
+          integral: false,
+          units: "BTC",
+        },
       },
     },
     advanced: {
@@ -174,6 +231,21 @@ export const getConfig: T.ExpectedExports.getConfig = async (effects) => {
       name: "Advanced",
       description: "Advanced Settings",
       spec: {
+        network: {
+          type: "enum",
+          name: "Network",
+          description:
+            "The Bitcoin network to run on. Each network keeps its own blockchain, so switching starts a new sync.",
+          values: ["main", "test", "testnet4", "signet", "regtest"],
+          "value-names": {
+            main: "Mainnet",
+            test: "Testnet3",
+            testnet4: "Testnet4",
+            signet: "Signet",
+            regtest: "Regtest",
+          },
+          default: "testnet4",
+        },
         mempool: {
           type: "object",
           name: "Mempool",
@@ -191,7 +263,7 @@ export const getConfig: T.ExpectedExports.getConfig = async (effects) => {
               name: "Max Mempool Size",
               description:
                 "Keep the transaction memory pool below <n> megabytes.",
-              range: "[1,*)",
+              range: "[5,*)",
               integral: true,
               units: "MiB",
               default: 300,
@@ -220,6 +292,13 @@ export const getConfig: T.ExpectedExports.getConfig = async (effects) => {
               description: "Relay non-P2SH multisig transactions",
               default: true,
             },
+            acceptnonstdtxn: {
+              type: "boolean",
+              name: "Accept Nonstandard Transactions",
+              description:
+                "Relay and mine transactions that break standardness policy, for testing them. Only allowed on test networks.",
+              default: false,
+            },
             datacarrier: {
               type: "boolean",
               name: "Relay OP_RETURN Transactions",
@@ -262,6 +341,24 @@ export const getConfig: T.ExpectedExports.getConfig = async (effects) => {
               description: "Only connect to peers over Tor.",
               default: false,
             },
+            proxy: {
+              type: "string",
+              nullable: true,
+              name: "Clearnet Proxy",
+              description:
+                "SOCKS5 proxy to reach clearnet peers through. Leave blank to connect directly, or through Tor when clearnet is disabled.",
+              pattern: "^[0-9.]+:[0-9]+$|^\\[[0-9a-fA-F:]+\\]:[0-9]+$",
+              "pattern-description": "Must be an IP address followed by a port.",
+            },
+            onion_proxy: {
+              type: "string",
+              nullable: true,
+              name: "Onion Proxy",
+              description:
+                "SOCKS5 proxy to reach onion peers through. Leave blank to use the embassy Tor proxy.",
+              pattern: "^[0-9.]+:[0-9]+$|^\\[[0-9a-fA-F:]+\\]:[0-9]+$",
+              "pattern-description": "Must be an IP address followed by a port.",
+            },
             v2transport: {
               type: "boolean",
               name: "Use V2 P2P Transport Protocol",
@@ -269,6 +366,46 @@ export const getConfig: T.ExpectedExports.getConfig = async (effects) => {
                 "Enable or disable the use of BIP324 V2 P2P transport protocol.",
               default: true,
             },
+            externalip: {
+              name: "Additional External Addresses",
+              description:
+                "Additional addresses (such as a clearnet IP or I2P address) to advertise to peers, on top of this service's Tor address.",
+              type: "list",
+              subtype: "string",
+              default: [],
+              spec: {
+                pattern: "^[a-zA-Z0-9.:\\[\\]-]+$",
+                "pattern-description":
+                  "Must be a hostname or IP address, optionally followed by a port.",
+              },
+              range: "[0,*)",
+            },
+            maxconnections: {
+              type: "number",
+              nullable: true,
+              name: "Max Connections",
+              description:
+                "The maximum number of inbound and outbound peer connections. Leave blank to use Bitcoin Core's default of 125, or to scale connections to memory when that is enabled.",
+              range: "[1,4096]",
+              integral: true,
+            },
+            auto_maxconnections: {
+              type: "boolean",
+              name: "Scale Connections to Memory",
+              description:
+                "Limit the number of peer connections based on the memory available when Bitcoin Core starts, after setting aside the database cache and mempool. Useful on low-memory devices.",
+              default: false,
+            },
+            maxuploadtarget: {
+              type: "number",
+              nullable: true,
+              name: "Max Upload Target",
+              description:
+                "Try to keep outbound traffic under the given target per 24h. Blocks requested by whitelisted peers are still served. Leave blank for no limit.",
+              range: "[576,*)",
+              integral: true,
+              units: "MiB",
+            },
             addnode: {
               name: "Add Nodes",
               description: "Add addresses of nodes to connect to.",
@@ -284,7 +421,7 @@ export const getConfig: T.ExpectedExports.getConfig = async (effects) => {
                     name: "Hostname",
                     description: "Domain or IP address of bitcoin peer",
                     pattern:
-                      "(^(?:(?:25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)\\.){3}(?:25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)$)|((^(([0-9a-fA-F]{1,4}:){7,7}[0-9a-fA-F]{1,4}|([0-9a-fA-F]{1,4}:){1,7}:|([0-9a-fA-F]{1,4}:){1,6}:[0-9a-fA-F]{1,4}|([0-9a-fA-F]{1,4}:){1,5}(:[0-9a-fA-F]{1,4}){1,2}|([0-9a-fA-F]{1,4}:){1,4}(:[0-9a-fA-F]{1,4}){1,3}|([0-9a-fA-F]{1,4}:){1,3}(:[0-9a-fA-F]{1,4}){1,4}|([0-9a-fA-F]{1,4}:){1,2}(:[0-9a-fA-F]{1,4}){1,5}|[0-9a-fA-F]{1,4}:((:[0-9a-fA-F]{1,4}){1,6})|:((:[0-9a-fA-F]{1,4}){1,7}|:)|fe80:(:[0-9a-fA-F]{0,4}){0,4}%[0-9a-zA-Z]{1,}|::(ffff(:0{1,4}){0,1}:){0,1}((25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])\\.){3,3}(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])|([0-9a-fA-F]{1,4}:){1,4}:((25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])\\.){3,3}(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9]))$)|(^[a-z2-7]{16}\\.onion$)|(^([a-z0-9]([a-z0-9-]{0,61}[a-z0-9])?\\.)+[a-z0-9][a-z0-9-]{0,61}[a-z0-9]$))",
+                      "(^(?:(?:25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)\\.){3}(?:25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)$)|((^(([0-9a-fA-F]{1,4}:){7,7}[0-9a-fA-F]{1,4}|([0-9a-fA-F]{1,4}:){1,7}:|([0-9a-fA-F]{1,4}:){1,6}:[0-9a-fA-F]{1,4}|([0-9a-fA-F]{1,4}:){1,5}(:[0-9a-fA-F]{1,4}){1,2}|([0-9a-fA-F]{1,4}:){1,4}(:[0-9a-fA-F]{1,4}){1,3}|([0-9a-fA-F]{1,4}:){1,3}(:[0-9a-fA-F]{1,4}){1,4}|([0-9a-fA-F]{1,4}:){1,2}(:[0-9a-fA-F]{1,4}){1,5}|[0-9a-fA-F]{1,4}:((:[0-9a-fA-F]{1,4}){1,6})|:((:[0-9a-fA-F]{1,4}){1,7}|:)|fe80:(:[0-9a-fA-F]{0,4}){0,4}%[0-9a-zA-Z]{1,}|::(ffff(:0{1,4}){0,1}:){0,1}((25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])\\.){3,3}(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])|([0-9a-fA-F]{1,4}:){1,4}:((25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])\\.){3,3}(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9]))$)|(^[a-z2-7]{56}\\.onion$)|(^([a-z0-9]([a-z0-9-]{0,61}[a-z0-9])?\\.)+[a-z0-9][a-z0-9-]{0,61}[a-z0-9]$))",
                     "pattern-description":
                       "Must be either a domain name, or an IPv4 or IPv6 address. Do not include protocol scheme (eg 'http://') or port.",
                   },
@@ -343,13 +480,166 @@ export const getConfig: T.ExpectedExports.getConfig = async (effects) => {
           nullable: true,
           name: "Database Cache",
           description:
-            "How much RAM to allocate for caching the TXO set. Higher values improve syncing performance, but increase your chance of using up all your system's memory or corrupting your database in the event of an ungraceful shutdown. Set this high but comfortably below your system's total RAM during IBD, then turn down to 450 (or leave blank) once the sync completes.",
+            "How much RAM to allocate for caching the TXO set. Higher values improve syncing performance, but increase your chance of using up all your system's memory or corrupting your database in the event of an ungraceful shutdown. Set this high but comfortably below your system's total RAM during IBD, then turn down to 450 (or leave blank) once the sync completes. When left blank, Bitcoin Core uses its own default of 450 MiB.",
           warning:
             "WARNING: Increasing this value results in a higher chance of ungraceful shutdowns, which can leave your node unusable if it happens during the initial block download. Use this setting with caution. Be sure to set this back to the default (450 or leave blank) once your node is synced. DO NOT press the STOP button if your dbcache is large. Instead, set this number back to the default, hit save, and wait for bitcoind to restart on its own.",
           range: "(0,*)",
           integral: true,
           units: "MiB",
         },
+        auto_reindex: {
+          type: "boolean",
+          name: "Automatic Reindex on Corruption",
+          description:
+            "When Bitcoin Core exits because of a corrupted database, schedule the narrowest repair for the next start: a chainstate reindex if only the UTXO set is affected, or a full reindex if the block files are.",
+          default: true,
+        },
+        blocksxor: {
+          type: "boolean",
+          name: "Obfuscate Block Files",
+          description:
+            "XOR-obfuscate block files on disk so that they are not flagged by antivirus or similar scanners. Only applies to block files of a new sync: existing unobfuscated blocks stay as they are, and obfuscation can't be turned off again without deleting and resyncing the blockchain. Requires Bitcoin Core 28.0 or newer.",
+          default: true,
+        },
+        min_version: {
+          type: "string",
+          nullable: true,
+          name: "Minimum Bitcoin Core Version",
+          description:
+            "Refuse to start if the bundled Bitcoin Core is older than this version, for deployments with a security floor. Leave blank to disable the check.",
+          pattern: "^v?[0-9]+(\\.[0-9]+){0,2}$",
+          "pattern-description": "Must be a version number such as 28.1 or 28.1.0.",
+        },
+        assumevalid: {
+          type: "string",
+          nullable: true,
+          name: "Assume Valid",
+          description:
+            "If this block is in the chain, assume that it and its ancestors are valid and potentially skip their script verification. Set to 0 to verify all scripts. Leave blank to use the block hash built into Bitcoin Core.",
+          pattern: "^(0|[0-9a-fA-F]{64})$",
+          "pattern-description": "Must be 0 or a 64 character block hash.",
+        },
+        proxy: {
+          type: "object",
+          name: "RPC Proxy",
+          description: "Settings for the RPC proxy in front of Bitcoin Core",
+          spec: {
+            enable: {
+              type: "boolean",
+              name: "Enable RPC Proxy",
+              description:
+                "Serve RPC through a proxy that fetches blocks from peers over Tor when they are missing locally. The proxy always runs when pruning is enabled.",
+              default: false,
+            },
+            "max-peer-concurrency": {
+              type: "number",
+              nullable: false,
+              name: "Max Peer Concurrency",
+              description:
+                "How many peers the proxy asks at once for a block that is missing locally. Higher values fetch pruned blocks faster but open more Tor circuits, adding load on Tor and on peers. Set to 0 for no limit.",
+              range: "[0,*)",
+              integral: true,
+              default: 1,
+            },
+            "peer-timeout-secs": {
+              type: "number",
+              nullable: false,
+              name: "Peer Timeout",
+              description:
+                "How long the proxy waits for a peer to respond before moving on to another. Raise this on slow Tor connections to avoid constantly switching peers.",
+              range: "[1,*)",
+              integral: true,
+              units: "seconds",
+              default: 30,
+            },
+            "max-peer-age-secs": {
+              type: "number",
+              nullable: false,
+              name: "Peer List Refresh",
+              description:
+                "How long the proxy keeps using its list of peers before fetching a fresh one from Bitcoin Core.",
+              range: "[1,*)",
+              integral: true,
+              units: "seconds",
+              default: 300,
+            },
+            "backpressure-secs": {
+              type: "number",
+              nullable: false,
+              name: "Backpressure Cooldown",
+              description:
+                "How long the proxy turns away requests after Bitcoin Core reports its RPC work queue full, instead of piling more onto it. Set to 0 to always forward requests.",
+              range: "[0,60]",
+              integral: true,
+              units: "seconds",
+              default: 2,
+            },
+          },
+        },
+        performance: {
+          type: "object",
+          name: "Performance",
+          description: "Resource usage settings",
+          spec: {
+            nice: {
+              type: "number",
+              nullable: true,
+              name: "CPU Priority (Niceness)",
+              description:
+                "Scheduling priority of Bitcoin Core. Higher values make it yield the CPU to other services, which is useful on shared hardware during the initial sync. Leave blank to run at normal priority.",
+              range: "[-20,19]",
+              integral: true,
+            },
+            par: {
+              type: "number",
+              nullable: true,
+              name: "Script Verification Threads",
+              description:
+                "The number of threads used to verify scripts. Leave blank or set to 0 to let Bitcoin Core use one per CPU core.",
+              range: "[0,15]",
+              integral: true,
+            },
+            adaptive_par: {
+              type: "boolean",
+              name: "Reduce Threads Under Load",
+              description:
+                "When the system is under high load as Bitcoin Core starts, only use the idle CPU cores for script verification so other services stay responsive.",
+              default: false,
+            },
+          },
+        },
+        zmq: {
+          type: "object",
+          name: "ZeroMQ Topics",
+          description:
+            "Which ZeroMQ topics to publish while ZeroMQ is enabled. With none selected, every topic is published, which is what LND and other dependent services expect.",
+          spec: {
+            rawblock: {
+              type: "boolean",
+              name: "Raw Blocks",
+              description: "Publish every new block in full on port 28332.",
+              default: false,
+            },
+            hashblock: {
+              type: "boolean",
+              name: "Block Hashes",
+              description: "Publish the hash of every new block on port 28332.",
+              default: false,
+            },
+            rawtx: {
+              type: "boolean",
+              name: "Raw Transactions",
+              description: "Publish every transaction entering the mempool or a block in full on port 28333.",
+              default: false,
+            },
+            hashtx: {
+              type: "boolean",
+              name: "Transaction Hashes",
+              description: "Publish the hash of every transaction entering the mempool or a block on port 28333.",
+              default: false,
+            },
+          },
+        },
         blockfilters: {
           type: "object",
           name: "Block Filters",
@@ -387,6 +677,267 @@ export const getConfig: T.ExpectedExports.getConfig = async (effects) => {
             },
           },
         },
+        mining: {
+          type: "object",
+          name: "Mining",
+          description:
+            "Block template settings for operators who mine against this node with getblocktemplate",
+          spec: {
+            blockmaxweight: {
+              type: "number",
+              nullable: true,
+              name: "Max Block Weight",
+              description:
+                "Set the maximum BIP141 block weight to be used when creating block templates. Leave blank to use the Bitcoin Core default.",
+              range: "[4000,4000000]",
+              integral: true,
+              units: "weight units",
+            },
+            blockmintxfee: {
+              type: "number",
+              nullable: true,
+              name: "Min Transaction Fee for Blocks",
+              description:
+                "Set the lowest fee rate for transactions to be included in block templates. Leave blank to use the Bitcoin Core default.",
+              range: "[0,1]",
+              integral: false,
+              units: "BTC/kvB",
+            },
+          },
+        },
+        signet: {
+          type: "object",
+          name: "Custom Signet",
+          description:
+            "Parameters for joining a custom signet. These only take effect when running on signet.",
+          spec: {
+            challenge: {
+              type: "string",
+              nullable: true,
+              name: "Signet Challenge",
+              description:
+                "The block challenge (usually a multisig script) that a block must satisfy to be valid on this signet. Leave blank to use the default global signet.",
+              pattern: "^([0-9a-fA-F]{2})+$",
+              "pattern-description": "Must be a hex encoded script.",
+            },
+            seednode: {
+              name: "Signet Seed Nodes",
+              description:
+                "Nodes to connect to in order to retrieve addresses of other peers on this signet.",
+              type: "list",
+              subtype: "string",
+              default: [],
+              spec: {
+                pattern: "^[a-zA-Z0-9.:\\[\\]-]+$",
+                "pattern-description":
+                  "Must be a hostname or IP address, optionally followed by a port.",
+              },
+              range: "[0,*)",
+            },
+          },
+        },
+        logging: {
+          type: "object",
+          name: "Logging",
+          description: "Logging Settings",
+          spec: {
+            debuglogfile: {
+              type: "string",
+              nullable: true,
+              name: "Debug Log File",
+              description:
+                "Write the debug log to this file, relative to the Bitcoin data directory. Missing directories are created. Leave blank to use the default location.",
+              pattern: "^[a-zA-Z0-9_-][a-zA-Z0-9_.-]*(/[a-zA-Z0-9_-][a-zA-Z0-9_.-]*)*$",
+              "pattern-description":
+                "Must be a relative path made of letters, numbers, dots, underscores and dashes, with no part starting with a dot.",
+            },
+          },
+        },
+        pidfile: {
+          type: "string",
+          nullable: true,
+          name: "PID File",
+          description:
+            "Write the process ID of Bitcoin Core to this file, relative to the Bitcoin data directory, so external scripts can find it. The file is removed when Bitcoin Core stops. Leave blank to not write one.",
+          pattern: "^[a-zA-Z0-9_-][a-zA-Z0-9_.-]*(/[a-zA-Z0-9_-][a-zA-Z0-9_.-]*)*$",
+          "pattern-description":
+            "Must be a relative path made of letters, numbers, dots, underscores and dashes, with no part starting with a dot.",
+        },
+        backup: {
+          type: "object",
+          name: "Backup",
+          description: "Backup Settings",
+          spec: {
+            ignore: {
+              name: "Excluded Directories",
+              description:
+                "Directories of the active chain's data that are left out of backups, such as blocks, chainstate and indexes. Remove an entry to back it up, or replace indexes with e.g. indexes/blockfilter and indexes/coinstats to back up only the transaction index. The blocks, chainstate and indexes of other networks are never backed up, but their wallets are.",
+              type: "list",
+              subtype: "string",
+              default: ["blocks", "chainstate", "indexes"],
+              spec: {
+                pattern: "^[a-zA-Z0-9_.-]+(/[a-zA-Z0-9_.-]+)*/?$",
+                "pattern-description":
+                  "Must be a directory path relative to the chain data directory.",
+              },
+              range: "[0,*)",
+            },
+          },
+        },
+        recovery: {
+          type: "object",
+          name: "Recovery",
+          description: "Recovery Settings",
+          spec: {
+            stall_minutes: {
+              type: "number",
+              nullable: false,
+              name: "Stall Threshold",
+              description:
+                "How long the initial sync may go without verifying a new block before it is considered stalled.",
+              range: "[10,*)",
+              integral: true,
+              units: "minutes",
+              default: 60,
+            },
+            auto_restart_on_stall: {
+              type: "boolean",
+              name: "Restart on Stalled Sync",
+              description:
+                "Cleanly restart Bitcoin Core when the initial sync stalls. Bitcoin Core is restarted at most 3 times until the sync completes, to avoid restart loops.",
+              default: false,
+            },
+            startup_retries: {
+              type: "number",
+              nullable: false,
+              name: "Startup Retries",
+              description:
+                "How many times to retry starting Bitcoin Core when it fails because a previous instance has not yet released its data directory lock or ports. Configuration errors are never retried.",
+              range: "[0,5]",
+              integral: true,
+              default: 3,
+            },
+          },
+        },
+        supervisor: {
+          type: "object",
+          name: "Crash Recovery",
+          description: "Restart Bitcoin Core automatically when it crashes",
+          spec: {
+            enable: {
+              type: "boolean",
+              name: "Restart on Crash",
+              description:
+                "Restart Bitcoin Core when it exits unexpectedly, waiting longer after each crash. Stopping the service and database corruption never trigger a restart.",
+              default: false,
+            },
+            max_restarts: {
+              type: "number",
+              nullable: false,
+              name: "Max Restarts",
+              description:
+                "How many times Bitcoin Core may be restarted within the restart window before giving up.",
+              range: "[1,10]",
+              integral: true,
+              default: 3,
+            },
+            window_minutes: {
+              type: "number",
+              nullable: false,
+              name: "Restart Window",
+              description:
+                "The period over which restarts are counted towards the maximum.",
+              range: "[1,*)",
+              integral: true,
+              units: "minutes",
+              default: 30,
+            },
+          },
+        },
+        shutdown: {
+          type: "object",
+          name: "Shutdown",
+          description: "Shutdown Settings",
+          spec: {
+            timeout_secs: {
+              type: "number",
+              nullable: false,
+              name: "Shutdown Timeout",
+              description:
+                "How long Bitcoin Core is given to flush its databases and exit before it is forcefully stopped. Increase this on slow storage or with a large database cache.",
+              warning:
+                "Forcefully stopping Bitcoin Core while it is still writing can corrupt its databases.",
+              range: "[10,290]",
+              integral: true,
+              units: "seconds",
+              default: 270,
+            },
+          },
+        },
+        stats: {
+          type: "object",
+          name: "Stats",
+          description: "Settings for the statistics shown in Properties",
+          spec: {
+            cli_client_timeout: {
+              type: "number",
+              nullable: false,
+              name: "RPC Client Timeout",
+              description:
+                "Number of seconds the statistics poller waits for a response from Bitcoin Core before giving up. Use 0 to wait indefinitely.",
+              range: "[0,3600]",
+              integral: true,
+              units: "seconds",
+              default: 900,
+            },
+            adaptive_polling: {
+              type: "boolean",
+              name: "Adaptive Polling",
+              description:
+                "Poll less often and skip expensive statistics while Bitcoin Core is slow to respond, to avoid adding to its load.",
+              default: false,
+            },
+            "poll-interval-seconds": {
+              type: "number",
+              nullable: true,
+              name: "Poll Interval",
+              description:
+                "How often statistics are refreshed. Increase this on low-power hardware to reduce load. Leave blank for the default of 5 seconds.",
+              range: "[1,*)",
+              integral: true,
+              units: "seconds",
+            },
+            format: {
+              type: "enum",
+              name: "Stats File Format",
+              description:
+                "The format the statistics are written in. JSON is written to start9/stats.json in the data directory. Properties are only shown in the UI when YAML is written.",
+              values: ["yaml", "json", "both"],
+              "value-names": {
+                yaml: "YAML",
+                json: "JSON",
+                both: "YAML and JSON",
+              },
+              default: "yaml",
+            },
+            socket: {
+              type: "string",
+              nullable: true,
+              name: "Stats Socket",
+              description:
+                "Also publish the latest statistics as JSON on a Unix domain socket at this path. Leave blank to disable.",
+              pattern: "^/[a-zA-Z0-9_./-]+$",
+              "pattern-description": "Must be an absolute path.",
+            },
+            mempoolrej: {
+              type: "boolean",
+              name: "Track Rejected Transactions",
+              description:
+                "Log transactions rejected from the mempool and show how many were rejected recently. Useful for diagnosing relay policy issues.",
+              default: false,
+            },
+          },
+        },
       },
     },
   })(effects);
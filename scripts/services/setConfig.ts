@@ -37,6 +37,28 @@ export const setConfig: types.ExpectedExports.setConfig = async (
     };
   }
 
+  if (
+    newConfig.advanced.mempool.acceptnonstdtxn &&
+    newConfig.advanced.network === "main"
+  ) {
+    return {
+      error:
+        "Accepting nonstandard transactions is only allowed on test networks.",
+    };
+  }
+
+  // Tor dropped v2 onion services, which have 16 character addresses
+  const legacyOnions = [
+    ...(newConfig.advanced.peers.externalip ?? []),
+    // deno-lint-ignore no-explicit-any
+    ...(newConfig.advanced.peers.addnode ?? []).map((node: any) => node.hostname),
+  ].filter((addr: string) => /^[a-z2-7]{16}\.onion(:[0-9]+)?$/.test(addr));
+  if (legacyOnions.length > 0) {
+    return {
+      error: `Legacy v2 onion addresses are no longer supported by Tor: ${legacyOnions.join(", ")}`,
+    };
+  }
+
   await effects.createDir({
     path: "start9",
     volumeId: "main",